@@ -0,0 +1,355 @@
+use crate::extraction::{
+    BoundingBox, Candidate, ExtractedField, ExtractionResult, value_text,
+};
+use crate::pdf::{PageSize, page_size};
+use crate::schema::SchemaField;
+use lopdf::{Dictionary, Document, Object, ObjectId, dictionary, text_string};
+
+/// Width and height of the popup note opened next to a highlight.
+const POPUP_SIZE: (f64, f64) = (220.0, 120.0);
+
+/// Opacity of the highlight colour, so the text underneath stays readable.
+const HIGHLIGHT_OPACITY: f64 = 0.5;
+
+/// Annotation flag that makes the highlight show up when printed.
+const PRINT_FLAG: i64 = 4;
+
+/// Highlight colour for each match type, as RGB components.
+fn match_colour(match_type: &str) -> [f64; 3] {
+    match match_type {
+        "found" => [0.55, 0.9, 0.45],
+        "inferred" => [1.0, 0.8, 0.3],
+        "ambiguous" => [0.75, 0.6, 0.95],
+        _ => [0.95, 0.45, 0.45],
+    }
+}
+
+fn popup_text(field_name: &str, field: &ExtractedField) -> String {
+    let value = field.value.as_ref().map(value_text).unwrap_or_default();
+    let mut text = format!(
+        "{field_name}\nValue: {value}\nMatch type: {}",
+        field.match_type
+    );
+    if let Some(comment) = field.comment.as_deref().filter(|c| !c.is_empty()) {
+        text.push_str("\nComment: ");
+        text.push_str(comment);
+    }
+    if !field.candidates.is_empty() {
+        let candidates: Vec<String> =
+            field.candidates.iter().map(Candidate::summary).collect();
+        text.push_str("\nCandidates: ");
+        text.push_str(&candidates.join(", "));
+    }
+    text
+}
+
+/// Popup text for a candidate highlighted away from the field's value.
+fn candidate_text(field_name: &str, candidate: &Candidate) -> String {
+    let mut text =
+        format!("{field_name}\nCandidate: {}", value_text(&candidate.value));
+    if let Some(evidence) = candidate.evidence.as_deref() {
+        text.push_str("\nEvidence: ");
+        text.push_str(evidence);
+    }
+    text
+}
+
+/// Writes a copy of the document with a highlight annotation over every
+/// extracted value that has a location.
+///
+/// Each highlight carries a popup note with the field name, value, match type
+/// and comment. Candidates found elsewhere in the paper get highlights of
+/// their own.
+///
+/// # Errors
+///
+/// Fails when a page or its annotations cannot be read, or the annotated
+/// PDF cannot be written.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "the binary imports it next to `load_pdf`, where a bare `pdf` would be unclear"
+)]
+#[inline]
+pub fn annotate_pdf(
+    document: &Document,
+    results: &ExtractionResult,
+    fields: &[SchemaField],
+) -> Result<Vec<u8>, String> {
+    let mut annotated = document.clone();
+    let pages = annotated.get_pages();
+
+    for schema_field in fields {
+        let Some(field) = results.get(&schema_field.field_name) else {
+            continue;
+        };
+        let name = &schema_field.field_name;
+        let mut highlights = Vec::new();
+        if let (Some(page), Some(bbox)) = (field.page, field.bbox) {
+            highlights.push((
+                page,
+                bbox,
+                popup_text(name, field),
+                match_colour(&field.match_type),
+            ));
+        }
+        for candidate in &field.candidates {
+            if let (Some(page), Some(bbox)) = (candidate.page, candidate.bbox)
+                && (field.page, field.bbox) != (Some(page), Some(bbox))
+            {
+                highlights.push((
+                    page,
+                    bbox,
+                    candidate_text(name, candidate),
+                    match_colour("ambiguous"),
+                ));
+            }
+        }
+
+        for (page, bbox, contents, colour) in highlights {
+            let (Some(&page_id), Some(size)) =
+                (pages.get(&page), page_size(&annotated, page))
+            else {
+                continue;
+            };
+            add_highlight(
+                &mut annotated,
+                page_id,
+                &size,
+                &bbox,
+                name,
+                &contents,
+                colour,
+            )?;
+        }
+    }
+
+    let mut data = Vec::new();
+    annotated
+        .save_to(&mut data)
+        .map_err(|e| format!("Failed to write annotated PDF: {e}"))?;
+    Ok(data)
+}
+
+/// Converts a top-left origin box to PDF user space corners
+/// `[left, bottom, right, top]`.
+#[expect(clippy::float_arithmetic, reason = "box geometry in points")]
+fn user_space_rect(size: &PageSize, bbox: &BoundingBox) -> [f64; 4] {
+    [
+        size.left + bbox.xmin,
+        size.top() - bbox.ymax,
+        size.left + bbox.xmax,
+        size.top() - bbox.ymin,
+    ]
+}
+
+#[expect(clippy::float_arithmetic, reason = "box geometry in points")]
+fn add_highlight(
+    document: &mut Document,
+    page_id: ObjectId,
+    size: &PageSize,
+    bbox: &BoundingBox,
+    title: &str,
+    contents: &str,
+    colour: [f64; 3],
+) -> Result<(), String> {
+    let [left, bottom, right, top] = user_space_rect(size, bbox);
+    let numbers = |values: &[f64]| -> Object {
+        Object::Array(values.iter().copied().map(Object::from).collect())
+    };
+
+    let highlight_id = document.new_object_id();
+    let (popup_width, popup_height) = POPUP_SIZE;
+    let popup_left = right.min(size.left + size.width - popup_width);
+    let popup_bottom = (top - popup_height).max(size.bottom);
+    let popup_id = document.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Popup",
+        "Parent" => highlight_id,
+        "Open" => false,
+        "Rect" => numbers(&[
+            popup_left,
+            popup_bottom,
+            popup_left + popup_width,
+            popup_bottom + popup_height,
+        ]),
+    });
+
+    document.objects.insert(
+        highlight_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Annot",
+            "Subtype" => "Highlight",
+            "Rect" => numbers(&[left, bottom, right, top]),
+            "QuadPoints" => numbers(&[
+                left, top, right, top, left, bottom, right, bottom,
+            ]),
+            "C" => numbers(&colour),
+            "CA" => HIGHLIGHT_OPACITY,
+            "F" => PRINT_FLAG,
+            "T" => text_string(title),
+            "Contents" => text_string(contents),
+            "Popup" => popup_id,
+            "P" => page_id,
+        }),
+    );
+
+    push_annotations(document, page_id, &[highlight_id, popup_id])
+}
+
+/// Appends annotation references to a page's `Annots` array, which may be
+/// missing, inline, or an indirect object.
+fn push_annotations(
+    document: &mut Document,
+    page_id: ObjectId,
+    annotation_ids: &[ObjectId],
+) -> Result<(), String> {
+    let references = annotation_ids.iter().map(|&id| Object::Reference(id));
+    let existing = document
+        .get_dictionary(page_id)
+        .map_err(|e| format!("Failed to read page: {e}"))?
+        .get(b"Annots")
+        .ok()
+        .cloned();
+
+    match existing {
+        Some(Object::Reference(array_id)) => {
+            document
+                .get_object_mut(array_id)
+                .and_then(Object::as_array_mut)
+                .map_err(|e| format!("Failed to read annotations: {e}"))?
+                .extend(references);
+        }
+        Some(Object::Array(mut annotations)) => {
+            annotations.extend(references);
+            page_dictionary(document, page_id)?
+                .set("Annots", Object::Array(annotations));
+        }
+        _ => {
+            page_dictionary(document, page_id)?
+                .set("Annots", Object::Array(references.collect()));
+        }
+    }
+    Ok(())
+}
+
+fn page_dictionary(
+    document: &mut Document,
+    page_id: ObjectId,
+) -> Result<&mut Dictionary, String> {
+    document
+        .get_dictionary_mut(page_id)
+        .map_err(|e| format!("Failed to read page: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::SchemaKind;
+    use serde_json::Value;
+
+    /// A blank 600 by 800 point page.
+    fn one_page_pdf() -> Document {
+        let mut document = Document::with_version("1.5");
+        let tree_id = document.new_object_id();
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => tree_id,
+            "MediaBox" => [0, 0, 600, 800].map(Object::Integer).to_vec(),
+        });
+        document.objects.insert(
+            tree_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![Object::Reference(page_id)],
+                "Count" => Object::Integer(1),
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => tree_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    fn located(match_type: &str, page: u32, ymin: f64) -> ExtractedField {
+        ExtractedField {
+            value: Some(Value::from("24")),
+            match_type: match_type.to_owned(),
+            comment: None,
+            page: Some(page),
+            bbox: Some(BoundingBox {
+                xmin: 100.0,
+                ymin,
+                xmax: 200.0,
+                ymax: 120.0,
+            }),
+            warnings: Vec::new(),
+            candidates: Vec::new(),
+        }
+    }
+
+    /// The numbers of an annotation array, rounded for comparison.
+    fn numbers(annotation: &Dictionary, key: &[u8]) -> Vec<String> {
+        annotation
+            .get(key)
+            .and_then(Object::as_array)
+            .unwrap()
+            .iter()
+            .map(|number| format!("{:.2}", number.as_float().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn highlights_located_fields_in_their_colour() {
+        let mut unlocated = located("found", 1, 100.0);
+        unlocated.bbox = None;
+        let results = ExtractionResult::from([
+            ("n".to_owned(), located("found", 1, 100.0)),
+            ("age".to_owned(), located("inferred", 1, 100.0)),
+            ("country".to_owned(), unlocated),
+            ("design".to_owned(), located("found", 5, 100.0)),
+        ]);
+        let fields = ["n", "age", "country", "design"]
+            .map(|name| SchemaField::new(name, SchemaKind::Text));
+
+        let data = annotate_pdf(&one_page_pdf(), &results, &fields).unwrap();
+        let annotated = Document::load_mem(&data).unwrap();
+        let page_id = annotated.get_pages()[&1];
+        let annotations: Vec<&Dictionary> = annotated
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Annots")
+            .and_then(Object::as_array)
+            .unwrap()
+            .iter()
+            .map(|reference| {
+                annotated
+                    .get_dictionary(reference.as_reference().unwrap())
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(annotations.len(), 4);
+
+        let highlights: Vec<&Dictionary> = annotations
+            .into_iter()
+            .filter(|annotation| {
+                annotation
+                    .get(b"Subtype")
+                    .and_then(Object::as_name)
+                    .unwrap()
+                    == b"Highlight"
+            })
+            .collect();
+        assert_eq!(highlights.len(), 2);
+        assert_eq!(
+            numbers(highlights[0], b"QuadPoints"),
+            [
+                "100.00", "700.00", "200.00", "700.00", "100.00", "680.00",
+                "200.00", "680.00"
+            ]
+        );
+        assert_eq!(numbers(highlights[0], b"C"), ["0.55", "0.90", "0.45"]);
+        assert_eq!(numbers(highlights[1], b"C"), ["1.00", "0.80", "0.30"]);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::schema::{SchemaField, SchemaKind};
+
+/// Output tokens every answer costs regardless of its value: the match
+/// type, page, bounding box and a short comment.
+const ANSWER_OVERHEAD_TOKENS: usize = 80;
+
+/// Output budget per request when batches are sized automatically.
+pub const DEFAULT_BATCH_TOKENS: usize = 2_000;
+
+/// How the schema is split into requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Batching {
+    /// At most this many fields per request.
+    Fields(usize),
+    /// As many fields as fit in this many estimated output tokens.
+    OutputTokens(usize),
+    /// Every field in a single request, so the paper is sent only once per
+    /// page window.
+    All,
+}
+
+impl Batching {
+    const fn cost(self, field: &SchemaField) -> usize {
+        match self {
+            Self::Fields(_) | Self::All => 1,
+            Self::OutputTokens(_) => estimate_output_tokens(field),
+        }
+    }
+
+    const fn limit(self) -> usize {
+        match self {
+            Self::Fields(limit) | Self::OutputTokens(limit) => limit,
+            Self::All => usize::MAX,
+        }
+    }
+}
+
+/// Estimates the output tokens of one field's answer from its kind.
+pub const fn estimate_output_tokens(field: &SchemaField) -> usize {
+    let value = match field.kind {
+        SchemaKind::Number => 8,
+        SchemaKind::Categorical => 16,
+        SchemaKind::Text => 64,
+    };
+    ANSWER_OVERHEAD_TOKENS.saturating_add(value)
+}
+
+/// Splits the schema into request batches, in schema order.
+///
+/// Fields sharing a `batch` key form one group, placed where the first of
+/// them appears; every other field is a group of its own. Groups are packed
+/// into batches up to the limit and never split, so a group larger than the
+/// limit is sent as a batch by itself.
+pub fn plan_batches(
+    fields: &[SchemaField],
+    batching: Batching,
+) -> Vec<Vec<SchemaField>> {
+    let mut groups: Vec<Vec<SchemaField>> = Vec::new();
+    for field in fields {
+        let shared = field.batch.as_deref().and_then(|key| {
+            groups.iter_mut().find(|group| {
+                group
+                    .first()
+                    .is_some_and(|first| first.batch.as_deref() == Some(key))
+            })
+        });
+        match shared {
+            Some(group) => group.push(field.clone()),
+            None => groups.push(vec![field.clone()]),
+        }
+    }
+
+    let mut batches: Vec<Vec<SchemaField>> = Vec::new();
+    let mut current: Vec<SchemaField> = Vec::new();
+    let mut current_cost: usize = 0;
+    for group in groups {
+        let group_cost: usize =
+            group.iter().map(|field| batching.cost(field)).sum();
+        if !current.is_empty()
+            && current_cost.saturating_add(group_cost) > batching.limit()
+        {
+            batches.push(current);
+            current = Vec::new();
+            current_cost = 0;
+        }
+        current.extend(group);
+        current_cost = current_cost.saturating_add(group_cost);
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, kind: SchemaKind, batch: Option<&str>) -> SchemaField {
+        SchemaField {
+            batch: batch.map(str::to_owned),
+            ..SchemaField::new(name, kind)
+        }
+    }
+
+    fn names(batches: &[Vec<SchemaField>]) -> Vec<Vec<&str>> {
+        batches
+            .iter()
+            .map(|batch| {
+                batch
+                    .iter()
+                    .map(|field| field.field_name.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grouped_fields_share_a_batch() {
+        let fields = [
+            field("dash_pre", SchemaKind::Number, Some("dash")),
+            field("age", SchemaKind::Number, Some("demographics")),
+            field("title", SchemaKind::Text, None),
+            field("dash_6w", SchemaKind::Number, Some("dash")),
+            field("sex", SchemaKind::Categorical, Some("demographics")),
+            field("dash_1y", SchemaKind::Number, Some("dash")),
+        ];
+
+        let batches = plan_batches(&fields, Batching::Fields(3));
+        assert_eq!(
+            names(&batches),
+            [
+                vec!["dash_pre", "dash_6w", "dash_1y"],
+                vec!["age", "sex", "title"],
+            ]
+        );
+    }
+
+    #[test]
+    fn automatic_batches_follow_the_token_budget() {
+        let fields = [
+            field("title", SchemaKind::Text, None),
+            field("design", SchemaKind::Categorical, None),
+            field("n", SchemaKind::Number, None),
+            field("summary", SchemaKind::Text, None),
+        ];
+        let budget = estimate_output_tokens(&fields[0])
+            + estimate_output_tokens(&fields[1])
+            + estimate_output_tokens(&fields[2]);
+
+        let batches = plan_batches(&fields, Batching::OutputTokens(budget));
+        assert_eq!(
+            names(&batches),
+            [vec!["title", "design", "n"], vec!["summary"]]
+        );
+    }
+}
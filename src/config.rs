@@ -0,0 +1,292 @@
+use crate::extraction::PrimaryRule;
+use crate::input::InputMode;
+use crate::output::OutputFormat;
+use crate::secret::{ApiKey, check_private};
+use core::{fmt, iter};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the config file, looked up in the working directory and then in
+/// the user's config directory.
+pub const CONFIG_FILE: &str = "paper-extract.toml";
+
+/// Run settings from the config file. Anything left out falls back to the
+/// command line defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct Settings {
+    /// OpenAI-compatible server to use instead of `OpenRouter`.
+    pub base_url: Option<String>,
+    /// Only allowed in a config file no one else can read.
+    pub api_key: Option<ApiKey>,
+    pub api_key_file: Option<PathBuf>,
+    pub model: Option<String>,
+    pub prompt_template: Option<PathBuf>,
+    pub domain_context: Option<PathBuf>,
+    pub examples: Option<PathBuf>,
+    pub example_tokens: Option<usize>,
+    pub batch: Option<BatchSetting>,
+    pub batch_tokens: Option<usize>,
+    pub concurrency: Option<usize>,
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+    pub format: Option<OutputFormat>,
+    pub input_mode: Option<InputMode>,
+    pub dpi: Option<u32>,
+    pub max_description_chars: Option<usize>,
+    pub primary: Option<PrimaryRule>,
+}
+
+impl Settings {
+    /// Takes each setting from `self`, or from `fallback` where `self` has
+    /// none.
+    fn or(self, fallback: Self) -> Self {
+        Self {
+            base_url: self.base_url.or(fallback.base_url),
+            api_key: self.api_key.or(fallback.api_key),
+            api_key_file: self.api_key_file.or(fallback.api_key_file),
+            model: self.model.or(fallback.model),
+            prompt_template: self.prompt_template.or(fallback.prompt_template),
+            domain_context: self.domain_context.or(fallback.domain_context),
+            examples: self.examples.or(fallback.examples),
+            example_tokens: self.example_tokens.or(fallback.example_tokens),
+            batch: self.batch.or(fallback.batch),
+            batch_tokens: self.batch_tokens.or(fallback.batch_tokens),
+            concurrency: self.concurrency.or(fallback.concurrency),
+            requests_per_minute: self
+                .requests_per_minute
+                .or(fallback.requests_per_minute),
+            tokens_per_minute: self
+                .tokens_per_minute
+                .or(fallback.tokens_per_minute),
+            format: self.format.or(fallback.format),
+            input_mode: self.input_mode.or(fallback.input_mode),
+            dpi: self.dpi.or(fallback.dpi),
+            max_description_chars: self
+                .max_description_chars
+                .or(fallback.max_description_chars),
+            primary: self.primary.or(fallback.primary),
+        }
+    }
+}
+
+/// The `batch` setting: a number of fields, or `"auto"` or `"all"` as on
+/// the command line.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum BatchSetting {
+    Fields(usize),
+    Named(String),
+}
+
+impl fmt::Display for BatchSetting {
+    #[expect(
+        clippy::pattern_type_mismatch,
+        reason = "the name is borrowed from the setting"
+    )]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fields(size) => write!(f, "{size}"),
+            Self::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+/// A parsed `paper-extract.toml`: top-level settings that apply to every
+/// run, and named `[profile.NAME]` tables layered over them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    defaults: Settings,
+    profiles: BTreeMap<String, Settings>,
+}
+
+impl Config {
+    /// Parses a config file's TOML, with profiles in `[profile.NAME]` tables.
+    ///
+    /// # Errors
+    ///
+    /// Fails on invalid TOML and on unknown or mistyped settings.
+    #[inline]
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut table: toml::Table =
+            toml::from_str(text).map_err(|e| e.to_string())?;
+        let profiles = table
+            .remove("profile")
+            .map(toml::Value::try_into)
+            .transpose()
+            .map_err(|e| format!("Invalid profile: {e}"))?
+            .unwrap_or_default();
+        let defaults = toml::Value::Table(table)
+            .try_into()
+            .map_err(|e| e.to_string())?;
+        Ok(Self { defaults, profiles })
+    }
+
+    /// Reads and parses a config file.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file cannot be read or does not parse.
+    #[inline]
+    pub fn from_path<P>(path: P) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        let text = fs::read_to_string(path.as_ref()).map_err(|e| {
+            format!(
+                "Failed to read config file {}: {e}",
+                path.as_ref().display()
+            )
+        })?;
+        let mut config = Self::parse(&text).map_err(|e| {
+            format!("Invalid config file {}: {e}", path.as_ref().display())
+        })?;
+        if config.has_api_key() {
+            check_private(path.as_ref())?;
+        }
+        if let Some(dir) = path.as_ref().parent() {
+            config.resolve_paths(dir);
+        }
+        Ok(config)
+    }
+
+    /// Makes file paths relative to the config file's folder.
+    fn resolve_paths(&mut self, dir: &Path) {
+        let settings =
+            iter::once(&mut self.defaults).chain(self.profiles.values_mut());
+        for entry in settings {
+            let paths = [
+                entry.api_key_file.as_mut(),
+                entry.prompt_template.as_mut(),
+                entry.domain_context.as_mut(),
+                entry.examples.as_mut(),
+            ];
+            for path in paths.into_iter().flatten() {
+                *path = dir.join(&*path);
+            }
+        }
+    }
+
+    fn has_api_key(&self) -> bool {
+        self.defaults.api_key.is_some()
+            || self
+                .profiles
+                .values()
+                .any(|settings| settings.api_key.is_some())
+    }
+
+    /// Loads the first config file found, if any: `paper-extract.toml` in
+    /// the working directory, then in `$XDG_CONFIG_HOME` (or
+    /// `~/.config`).
+    ///
+    /// # Errors
+    ///
+    /// Fails when a config file is found but cannot be read or parsed.
+    #[inline]
+    pub fn discover() -> Result<Option<Self>, String> {
+        let config_home = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME")
+                    .map(|home| PathBuf::from(home).join(".config"))
+            });
+        let candidates = [
+            Some(PathBuf::from(CONFIG_FILE)),
+            config_home.map(|dir| dir.join(CONFIG_FILE)),
+        ];
+        candidates
+            .into_iter()
+            .flatten()
+            .find(|path| path.is_file())
+            .map(Self::from_path)
+            .transpose()
+    }
+
+    /// The settings for a run: the named profile, if any, over the
+    /// top-level settings.
+    ///
+    /// # Errors
+    ///
+    /// Fails when `profile` names a profile the config does not have.
+    #[inline]
+    pub fn settings(&self, profile: Option<&str>) -> Result<Settings, String> {
+        let Some(name) = profile else {
+            return Ok(self.defaults.clone());
+        };
+        let selected = self.profiles.get(name).ok_or_else(|| {
+            let known: Vec<&str> =
+                self.profiles.keys().map(String::as_str).collect();
+            format!(
+                "Unknown profile '{name}' (config has: {})",
+                known.join(", ")
+            )
+        })?;
+        Ok(selected.clone().or(self.defaults.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+model = "openai/gpt-5-mini"
+concurrency = 4
+format = "jsonl"
+
+[profile.cheap]
+model = "google/gemini-2.5-flash-lite"
+batch = "all"
+
+[profile.accurate]
+model = "anthropic/claude-sonnet-4.5"
+batch = 5
+input-mode = "images"
+"#;
+
+    #[test]
+    fn profiles_override_top_level_settings() {
+        let config = Config::parse(CONFIG).unwrap();
+
+        let defaults = config.settings(None).unwrap();
+        assert_eq!(defaults.model.as_deref(), Some("openai/gpt-5-mini"));
+        assert_eq!(defaults.batch, None);
+
+        let cheap = config.settings(Some("cheap")).unwrap();
+        assert_eq!(
+            cheap.model.as_deref(),
+            Some("google/gemini-2.5-flash-lite")
+        );
+        assert_eq!(
+            cheap.batch.map(|batch| batch.to_string()).as_deref(),
+            Some("all")
+        );
+        assert_eq!(cheap.concurrency, Some(4));
+        assert_eq!(cheap.format, Some(OutputFormat::Jsonl));
+
+        let accurate = config.settings(Some("accurate")).unwrap();
+        assert_eq!(accurate.batch, Some(BatchSetting::Fields(5)));
+        assert_eq!(accurate.input_mode, Some(InputMode::Images));
+
+        assert!(
+            config
+                .settings(Some("fast"))
+                .unwrap_err()
+                .contains("accurate, cheap")
+        );
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        let typo = Config::parse("modle = \"x\"").unwrap_err();
+        assert!(typo.contains("modle"), "{typo}");
+
+        let in_profile =
+            Config::parse("[profile.cheap]\nbatch-size = 5").unwrap_err();
+        assert!(in_profile.contains("batch-size"), "{in_profile}");
+    }
+}
@@ -0,0 +1,323 @@
+use crate::extraction::BoundingBox;
+use crate::output::{OutputRow, read_output};
+use crate::pdf::load_pdf;
+use crate::ratelimit::estimate_text_tokens;
+use crate::schema::SchemaField;
+use crate::text::{TextRun, evidence_text, page_runs};
+use lopdf::Document;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Token budget for the examples of one request when none is set.
+pub const DEFAULT_EXAMPLE_TOKENS: usize = 1_000;
+
+/// Most examples shown for one field, however much budget is left.
+const MAX_EXAMPLES_PER_FIELD: usize = 3;
+
+/// Longest snippet quoted from an example paper, in characters.
+const MAX_SNIPPET_CHARS: usize = 240;
+
+/// Older output CSVs wrote missing values as `NULL`.
+const NULL: &str = "NULL";
+
+/// A reviewed answer from another paper, shown to the model as an example
+/// of how a field is filled in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Example {
+    /// File name of the paper it comes from, without the extension.
+    pub paper: String,
+    pub value: String,
+    pub match_type: String,
+    pub comment: String,
+    /// The line of the paper the value was found on, empty when the PDF is
+    /// not next to the CSV or the value has no location.
+    pub snippet: String,
+    /// Whether a person checked the value with `review`.
+    pub reviewed: bool,
+}
+
+impl Example {
+    fn render(&self) -> String {
+        let mut line = format!(
+            "  Example from {}: \"{}\" ({})",
+            self.paper, self.value, self.match_type
+        );
+        if !self.snippet.is_empty() {
+            write!(&mut line, ", from the text \"{}\"", self.snippet).unwrap();
+        }
+        if !self.comment.is_empty() {
+            write!(&mut line, "; comment: {}", self.comment).unwrap();
+        }
+        line.push('\n');
+        line
+    }
+
+    /// Better examples sort first: checked by a person, quoted from the
+    /// paper, and found rather than inferred.
+    fn rank(&self) -> (bool, bool, bool) {
+        (
+            !self.reviewed,
+            self.snippet.is_empty(),
+            self.match_type != "found",
+        )
+    }
+}
+
+/// Examples for each schema field, drawn from earlier extractions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Examples {
+    by_field: BTreeMap<String, Vec<Example>>,
+    max_tokens: usize,
+}
+
+impl Examples {
+    /// Reads every output CSV in `dir`, preferring `paper.reviewed.csv` over
+    /// `paper.csv`, and keeps the values that were found or inferred. A
+    /// `paper.pdf` next to them supplies the text around each value.
+    /// `max_tokens` caps the examples added to one request.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the directory, one of its CSVs or a PDF next to them cannot
+    /// be read.
+    #[inline]
+    pub fn from_dir<P>(path: P, max_tokens: usize) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        let dir = path.as_ref();
+        let mut by_field: BTreeMap<String, Vec<Example>> = BTreeMap::new();
+        for (paper, (csv, reviewed)) in example_files(dir)? {
+            let rows = read_output(&csv)?;
+            let pdf = dir.join(format!("{paper}.pdf"));
+            let document = if pdf.is_file() {
+                let data = fs::read(&pdf).map_err(|e| {
+                    format!("Failed to read {}: {e}", pdf.display())
+                })?;
+                Some(load_pdf(&data)?)
+            } else {
+                None
+            };
+            let mut runs_by_page = BTreeMap::new();
+
+            for row in rows {
+                let value = present(&row.value);
+                if value.is_empty()
+                    || !matches!(row.match_type.as_str(), "found" | "inferred")
+                {
+                    continue;
+                }
+                let snippet = document
+                    .as_ref()
+                    .map(|loaded| line_text(loaded, &row, &mut runs_by_page))
+                    .unwrap_or_default();
+                by_field.entry(row.field_name.clone()).or_default().push(
+                    Example {
+                        paper: paper.clone(),
+                        value: value.to_owned(),
+                        match_type: row.match_type.clone(),
+                        comment: present(&row.comment).to_owned(),
+                        snippet,
+                        reviewed,
+                    },
+                );
+            }
+        }
+
+        for examples in by_field.values_mut() {
+            examples.sort_by_key(Example::rank);
+        }
+        Ok(Self {
+            by_field,
+            max_tokens,
+        })
+    }
+
+    /// Number of examples across all fields.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.by_field.values().map(Vec::len).sum()
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.by_field.is_empty()
+    }
+
+    /// Picks the examples for one request's fields within the token budget
+    /// and renders them, keyed by field name. Every field gets its best
+    /// example before any field gets a second. Examples from
+    /// `exclude_paper` are left out, so a paper never answers itself.
+    #[must_use]
+    #[inline]
+    pub fn select(
+        &self,
+        fields: &[SchemaField],
+        exclude_paper: &str,
+    ) -> BTreeMap<String, String> {
+        let excluded = Path::new(exclude_paper)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut budget = self.max_tokens;
+        let mut chosen: BTreeMap<String, String> = BTreeMap::new();
+
+        for round in 0..MAX_EXAMPLES_PER_FIELD {
+            for field in fields {
+                let Some(example) =
+                    self.by_field.get(&field.field_name).and_then(|examples| {
+                        examples
+                            .iter()
+                            .filter(|example| example.paper != excluded)
+                            .nth(round)
+                    })
+                else {
+                    continue;
+                };
+                let line = example.render();
+                let cost = estimate_text_tokens(&line);
+                if cost > budget {
+                    continue;
+                }
+                budget = budget.saturating_sub(cost);
+                chosen
+                    .entry(field.field_name.clone())
+                    .or_default()
+                    .push_str(&line);
+            }
+        }
+        chosen
+    }
+}
+
+/// The CSV to read for each paper in `dir`, and whether it was reviewed.
+fn example_files(
+    dir: &Path,
+) -> Result<BTreeMap<String, (PathBuf, bool)>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {e}", dir.display()))?;
+    let mut files = BTreeMap::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Failed to read {}: {e}", dir.display()))?
+            .path();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some(paper) = name.strip_suffix(".reviewed.csv") {
+            files.insert(paper.to_owned(), (path, true));
+        } else if let Some(paper) = name.strip_suffix(".csv") {
+            files.entry(paper.to_owned()).or_insert((path, false));
+        } else {
+            // Not an output CSV, such as the paper's PDF.
+        }
+    }
+    if files.is_empty() {
+        return Err(format!("No example CSV files found in {}", dir.display()));
+    }
+    Ok(files)
+}
+
+/// The full line of text a located value sits on, shortened to
+/// [`MAX_SNIPPET_CHARS`]. Empty unless the value appears in it, so a box
+/// that missed its value does not teach the model the wrong text.
+fn line_text(
+    document: &Document,
+    row: &OutputRow,
+    runs_by_page: &mut BTreeMap<u32, Vec<TextRun>>,
+) -> String {
+    let (Some(page), Some(bbox)) = (row.page_number(), row.bbox()) else {
+        return String::new();
+    };
+    let runs = runs_by_page
+        .entry(page)
+        .or_insert_with(|| page_runs(document, page).unwrap_or_default());
+    let line = BoundingBox {
+        xmin: f64::MIN,
+        xmax: f64::MAX,
+        ..bbox
+    };
+    let text = evidence_text(runs, &line)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !normalize(&text).contains(&normalize(&row.value)) {
+        return String::new();
+    }
+    if text.chars().count() > MAX_SNIPPET_CHARS {
+        let mut shortened: String =
+            text.chars().take(MAX_SNIPPET_CHARS).collect();
+        shortened.push_str("...");
+        shortened
+    } else {
+        text
+    }
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn present(text: &str) -> &str {
+    if text == NULL { "" } else { text }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::SchemaKind;
+    use crate::testing::TestDir;
+
+    fn field(name: &str) -> SchemaField {
+        SchemaField::new(name, SchemaKind::Text)
+    }
+
+    #[test]
+    fn reviewed_examples_from_other_papers_come_first() {
+        let dir = TestDir::new("examples");
+        let header =
+            "field_name,value,match_type,comment,page,xmin,ymin,xmax,ymax\n";
+        fs::write(
+            dir.join("smith.csv"),
+            format!("{header}Design,RCT,found,NULL,1,0,0,0,0\nCountry,NULL,not_found,NULL,,,,,\n"),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("jones.csv"),
+            format!("{header}Design,Cohort,inferred,Unclear,,,,,\n"),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("jones.reviewed.csv"),
+            format!("{header}Design,Retrospective cohort,found,NULL,,,,,\n"),
+        )
+        .unwrap();
+
+        let examples =
+            Examples::from_dir(dir.path(), DEFAULT_EXAMPLE_TOKENS).unwrap();
+        assert_eq!(examples.len(), 2);
+
+        let fields = [field("Design"), field("Country")];
+        let chosen = examples.select(&fields, "new.pdf");
+        assert_eq!(
+            chosen["Design"],
+            "  Example from jones: \"Retrospective cohort\" (found)\n  Example from smith: \"RCT\" (found)\n"
+        );
+        assert!(!chosen.contains_key("Country"));
+
+        let own_paper = examples.select(&fields, "jones.pdf");
+        assert!(!own_paper["Design"].contains("jones"));
+
+        let capped = Examples::from_dir(dir.path(), 15)
+            .unwrap()
+            .select(&fields, "new.pdf");
+        assert_eq!(capped["Design"].lines().count(), 1);
+    }
+}
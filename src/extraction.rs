@@ -0,0 +1,737 @@
+use crate::pdf::PageSize;
+use crate::render::pixels_to_points;
+use crate::schema::Condition;
+use clap::ValueEnum;
+use core::cmp::Ordering;
+use core::mem;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// A field as returned by the model, before its location has been checked
+/// against the document.
+#[derive(Debug, Deserialize)]
+pub struct RawField {
+    pub value: Option<Value>,
+    pub match_type: String,
+    pub comment: Option<String>,
+    /// Other values the paper reports for the field, such as the conflicting
+    /// values of an `ambiguous` field.
+    #[serde(default)]
+    pub candidates: Vec<RawCandidate>,
+    pub page: Option<i64>,
+    pub xmin: Option<f64>,
+    pub ymin: Option<f64>,
+    pub xmax: Option<f64>,
+    pub ymax: Option<f64>,
+}
+
+/// A candidate value as returned by the model.
+#[derive(Debug, Deserialize)]
+pub struct RawCandidate {
+    pub value: Value,
+    pub page: Option<i64>,
+    pub xmin: Option<f64>,
+    pub ymin: Option<f64>,
+    pub xmax: Option<f64>,
+    pub ymax: Option<f64>,
+    pub evidence: Option<String>,
+}
+
+pub type RawExtraction = BTreeMap<String, RawField>;
+
+/// A rectangle on a page in PDF points, origin at the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct BoundingBox {
+    pub xmin: f64,
+    pub ymin: f64,
+    pub xmax: f64,
+    pub ymax: f64,
+}
+
+/// A field whose page is known to exist and whose box lies on that page.
+/// Any corrections made while validating the model's answer are kept in
+/// `warnings` so they can be shown to a reviewer.
+#[derive(Debug, Clone, Serialize)]
+#[non_exhaustive]
+pub struct ExtractedField {
+    pub value: Option<Value>,
+    pub match_type: String,
+    pub comment: Option<String>,
+    pub page: Option<u32>,
+    pub bbox: Option<BoundingBox>,
+    pub warnings: Vec<String>,
+    /// Every value the paper reports for the field, `value` among them.
+    /// Empty unless the field is `ambiguous` or its schema asks for
+    /// candidates.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub candidates: Vec<Candidate>,
+}
+
+/// One value the paper reports for a field, with where it was found.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Candidate {
+    pub value: Value,
+    pub page: Option<u32>,
+    pub bbox: Option<BoundingBox>,
+    /// The text the value was read from, as quoted by the model.
+    pub evidence: Option<String>,
+}
+
+impl Candidate {
+    /// The value and its page, such as `24 (page 3)`.
+    #[must_use]
+    #[inline]
+    pub fn summary(&self) -> String {
+        let value = value_text(&self.value);
+        match self.page {
+            Some(page) => format!("{value} (page {page})"),
+            None => value,
+        }
+    }
+}
+
+/// How the primary value of a field with several candidates is chosen.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum PrimaryRule {
+    /// Keep the value the model gave as its answer.
+    #[default]
+    Model,
+    /// The candidate that appears first in the paper.
+    First,
+    /// The candidate that appears last in the paper, such as the results
+    /// rather than the abstract.
+    Last,
+    /// The value reported most often, keeping the model's answer on a tie.
+    MostCommon,
+}
+
+pub type ExtractionResult = BTreeMap<String, ExtractedField>;
+
+impl ExtractedField {
+    /// The answer for a conditional field whose condition did not hold, so
+    /// it was never asked for.
+    #[must_use]
+    #[inline]
+    pub fn not_applicable(condition: &Condition) -> Self {
+        Self {
+            value: None,
+            match_type: "not_applicable".to_owned(),
+            comment: Some(format!(
+                "Not applicable: condition {condition} does not hold"
+            )),
+            page: None,
+            bbox: None,
+            warnings: Vec::new(),
+            candidates: Vec::new(),
+        }
+    }
+
+    /// Makes the candidate picked by `rule` the field's value and location.
+    /// A found or inferred field whose candidates disagree becomes
+    /// `ambiguous`, so a reviewer sees the conflict.
+    #[inline]
+    pub fn choose_primary(&mut self, rule: PrimaryRule) {
+        let Some(first) = self.candidates.first() else {
+            return;
+        };
+        let conflicting = self
+            .candidates
+            .iter()
+            .any(|candidate| candidate.value != first.value);
+        if conflicting
+            && matches!(self.match_type.as_str(), "found" | "inferred")
+        {
+            "ambiguous".clone_into(&mut self.match_type);
+        }
+
+        let located = || {
+            self.candidates
+                .iter()
+                .filter(|candidate| candidate.page.is_some())
+        };
+        let chosen = match rule {
+            PrimaryRule::Model => None,
+            PrimaryRule::First => located().min_by(|a, b| position(a, b)),
+            PrimaryRule::Last => located().max_by(|a, b| position(a, b)),
+            PrimaryRule::MostCommon => {
+                let count = |value: &Value| {
+                    self.candidates
+                        .iter()
+                        .filter(|candidate| candidate.value == *value)
+                        .count()
+                };
+                let current = self.value.as_ref().map_or(0, count);
+                self.candidates
+                    .iter()
+                    .rev()
+                    .filter(|candidate| count(&candidate.value) > current)
+                    .max_by_key(|candidate| count(&candidate.value))
+            }
+        };
+        if let Some(candidate) = chosen.cloned() {
+            self.value = Some(candidate.value);
+            self.page = candidate.page;
+            self.bbox = candidate.bbox;
+        }
+    }
+
+    /// Keeps at most `limit` candidates in paper order, always including the
+    /// one chosen as the value and, when the candidates disagree, the first
+    /// that differs from it, so a capped list still shows the conflict.
+    /// Those two are kept even when `limit` is smaller.
+    #[inline]
+    pub fn limit_candidates(&mut self, limit: usize) {
+        if self.candidates.len() <= limit {
+            return;
+        }
+        let value = self.value.as_ref();
+        let primary = self.candidates.iter().position(|candidate| {
+            Some(&candidate.value) == value && candidate.page == self.page
+        });
+        let conflict = self
+            .candidates
+            .iter()
+            .position(|candidate| Some(&candidate.value) != value);
+        let required = [primary, conflict];
+        let mut spare = limit.saturating_sub(required.iter().flatten().count());
+        self.candidates = mem::take(&mut self.candidates)
+            .into_iter()
+            .enumerate()
+            .filter(|&(index, _)| {
+                if required.contains(&Some(index)) {
+                    return true;
+                }
+                let keep = spare > 0;
+                spare = spare.saturating_sub(1);
+                keep
+            })
+            .map(|(_, candidate)| candidate)
+            .collect();
+    }
+}
+
+/// Orders candidates by where they appear: page, then distance from the
+/// top of the page.
+fn position(a: &Candidate, b: &Candidate) -> Ordering {
+    let top = |candidate: &Candidate| {
+        candidate.bbox.map_or(f64::INFINITY, |bbox| bbox.ymin)
+    };
+    a.page.cmp(&b.page).then(top(a).total_cmp(&top(b)))
+}
+
+impl RawField {
+    /// Converts a box measured on a page image rendered at `dpi` to points.
+    pub fn pixels_to_points(&mut self, dpi: u32) {
+        for coordinate in [
+            &mut self.xmin,
+            &mut self.ymin,
+            &mut self.xmax,
+            &mut self.ymax,
+        ] {
+            *coordinate =
+                coordinate.map(|pixels| pixels_to_points(pixels, dpi));
+        }
+        for candidate in &mut self.candidates {
+            for coordinate in [
+                &mut candidate.xmin,
+                &mut candidate.ymin,
+                &mut candidate.xmax,
+                &mut candidate.ymax,
+            ] {
+                *coordinate =
+                    coordinate.map(|pixels| pixels_to_points(pixels, dpi));
+            }
+        }
+    }
+
+    /// Maps the page of the field and of each candidate, such as from a
+    /// page window back to the whole document.
+    pub fn map_pages<F>(&mut self, map: F)
+    where
+        F: Fn(i64) -> i64,
+    {
+        self.page = self.page.map(&map);
+        for candidate in &mut self.candidates {
+            candidate.page = candidate.page.map(&map);
+        }
+    }
+}
+
+/// Checks the page and box reported by the model against the real page
+/// sizes.
+///
+/// Pages outside the document drop the location entirely. Boxes are put in
+/// min/max order and clamped to the page; boxes that are partial, not finite,
+/// all zero or entirely off the page are dropped. Every correction other than
+/// dropping an all-zero placeholder box is recorded as a warning.
+pub fn validate_field(
+    raw: RawField,
+    pages: &BTreeMap<u32, PageSize>,
+) -> ExtractedField {
+    let mut warnings = Vec::new();
+    let (page, bbox) = validate_location(
+        raw.page,
+        [raw.xmin, raw.ymin, raw.xmax, raw.ymax],
+        pages,
+        &mut warnings,
+    );
+
+    let mut candidates: Vec<Candidate> = Vec::new();
+    let first_number: usize = 1;
+    for (number, candidate) in (first_number..).zip(raw.candidates) {
+        if candidate.value.is_null() {
+            continue;
+        }
+        let mut problems = Vec::new();
+        let (candidate_page, candidate_box) = validate_location(
+            candidate.page,
+            [
+                candidate.xmin,
+                candidate.ymin,
+                candidate.xmax,
+                candidate.ymax,
+            ],
+            pages,
+            &mut problems,
+        );
+        warnings.extend(
+            problems
+                .into_iter()
+                .map(|problem| format!("candidate {number}: {problem}")),
+        );
+        candidates.push(Candidate {
+            value: candidate.value,
+            page: candidate_page,
+            bbox: candidate_box,
+            evidence: candidate
+                .evidence
+                .filter(|evidence| !evidence.trim().is_empty()),
+        });
+    }
+    if let Some(value) = raw.value.as_ref().filter(|value| !value.is_null())
+        && !candidates.is_empty()
+        && !candidates.iter().any(|candidate| candidate.value == *value)
+    {
+        candidates.insert(
+            0,
+            Candidate {
+                value: value.clone(),
+                page,
+                bbox,
+                evidence: None,
+            },
+        );
+    }
+
+    ExtractedField {
+        value: raw.value,
+        match_type: raw.match_type,
+        comment: raw.comment,
+        page,
+        bbox,
+        warnings,
+        candidates,
+    }
+}
+
+/// Checks a page and box against the page sizes, returning what is valid
+/// and describing any correction in `warnings`.
+fn validate_location(
+    raw_page: Option<i64>,
+    coordinates: [Option<f64>; 4],
+    pages: &BTreeMap<u32, PageSize>,
+    warnings: &mut Vec<String>,
+) -> (Option<u32>, Option<BoundingBox>) {
+    let page = raw_page.and_then(|number| {
+        let valid = u32::try_from(number)
+            .ok()
+            .filter(|page_number| pages.contains_key(page_number));
+        if valid.is_none() {
+            warnings.push(format!(
+                "page {number} is outside the document (1-{})",
+                pages.len()
+            ));
+        }
+        valid
+    });
+
+    let bbox = match coordinates {
+        [None, None, None, None] => None,
+        [Some(xmin), Some(ymin), Some(xmax), Some(ymax)] => {
+            let size = page.and_then(|number| pages.get(&number));
+            normalize_box([xmin, ymin, xmax, ymax], size, warnings)
+        }
+        _ => {
+            warnings.push("bounding box is incomplete".to_owned());
+            None
+        }
+    };
+    (page, page.and(bbox))
+}
+
+#[expect(clippy::float_arithmetic, reason = "box geometry in points")]
+fn normalize_box(
+    [x0, y0, x1, y1]: [f64; 4],
+    page: Option<&PageSize>,
+    warnings: &mut Vec<String>,
+) -> Option<BoundingBox> {
+    if [x0, y0, x1, y1]
+        .iter()
+        .any(|coordinate| !coordinate.is_finite())
+    {
+        warnings.push("bounding box has non-finite coordinates".to_owned());
+        return None;
+    }
+    if [x0, y0, x1, y1]
+        .iter()
+        .all(|coordinate| coordinate.abs() < f64::EPSILON)
+    {
+        return None;
+    }
+    if x0 > x1 || y0 > y1 {
+        warnings.push("bounding box corners were swapped".to_owned());
+    }
+
+    let unclamped = BoundingBox {
+        xmin: x0.min(x1),
+        ymin: y0.min(y1),
+        xmax: x0.max(x1),
+        ymax: y0.max(y1),
+    };
+    let Some(size) = page else {
+        return Some(unclamped);
+    };
+
+    let clamped = BoundingBox {
+        xmin: unclamped.xmin.clamp(0.0, size.width),
+        ymin: unclamped.ymin.clamp(0.0, size.height),
+        xmax: unclamped.xmax.clamp(0.0, size.width),
+        ymax: unclamped.ymax.clamp(0.0, size.height),
+    };
+    if (clamped.xmax - clamped.xmin).min(clamped.ymax - clamped.ymin)
+        < f64::EPSILON
+    {
+        warnings.push("bounding box lies outside the page".to_owned());
+        return None;
+    }
+    if clamped != unclamped {
+        warnings.push("bounding box was clamped to the page".to_owned());
+    }
+    Some(clamped)
+}
+
+/// Renders a value the way it appears in a CSV cell: strings unquoted,
+/// nulls empty and anything else as JSON.
+pub fn value_text(value: &Value) -> String {
+    if let Some(string_val) = value.as_str() {
+        string_val.to_owned()
+    } else if value.is_null() {
+        String::new()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Ranks match types so that a `found` value from one page window wins over
+/// an `inferred` or `not_found` value from another. Conflicting values seen
+/// in one window outrank both, since a single value found elsewhere does
+/// not settle the conflict.
+fn match_rank(match_type: &str) -> u8 {
+    match match_type {
+        "ambiguous" => 3,
+        "found" => 2,
+        "inferred" => 1,
+        _ => 0,
+    }
+}
+
+/// Adds a field to the merged result unless a better-ranked answer for it
+/// is already there.
+///
+/// Between answers of the same rank the one on the earlier page wins, so the
+/// result does not depend on which page window's request finished first.
+/// Either way, the candidates of both answers are kept in the order they
+/// appear in the paper, less any that repeat a kept value without adding a
+/// location.
+pub fn merge_field(
+    merged: &mut ExtractionResult,
+    field_name: String,
+    field: ExtractedField,
+) {
+    let Some(existing) = merged.get_mut(&field_name) else {
+        merged.insert(field_name, field);
+        return;
+    };
+    let mut other = field;
+    let page = |answer: &ExtractedField| answer.page.unwrap_or(u32::MAX);
+    let better = match_rank(&other.match_type)
+        .cmp(&match_rank(&existing.match_type))
+        .then_with(|| page(existing).cmp(&page(&other)));
+    if better.is_gt() {
+        mem::swap(existing, &mut other);
+    }
+    for candidate in other.candidates {
+        let known = existing.candidates.iter().any(|kept| {
+            kept.value == candidate.value
+                && (kept.page == candidate.page || candidate.page.is_none())
+        });
+        if !known {
+            existing.candidates.push(candidate);
+        }
+    }
+    existing.candidates.sort_by(position);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pages() -> BTreeMap<u32, PageSize> {
+        let size = PageSize {
+            left: 0.0,
+            bottom: 0.0,
+            width: 600.0,
+            height: 800.0,
+        };
+        BTreeMap::from([(1, size), (2, size)])
+    }
+
+    fn corners(xmin: f64, ymin: f64, xmax: f64, ymax: f64) -> [Option<f64>; 4] {
+        [Some(xmin), Some(ymin), Some(xmax), Some(ymax)]
+    }
+
+    fn boxed(xmin: f64, ymin: f64, xmax: f64, ymax: f64) -> BoundingBox {
+        BoundingBox {
+            xmin,
+            ymin,
+            xmax,
+            ymax,
+        }
+    }
+
+    fn raw(page: Option<i64>, coordinates: [Option<f64>; 4]) -> RawField {
+        let [xmin, ymin, xmax, ymax] = coordinates;
+        RawField {
+            value: Some(Value::from(17)),
+            match_type: "found".to_owned(),
+            comment: None,
+            candidates: Vec::new(),
+            page,
+            xmin,
+            ymin,
+            xmax,
+            ymax,
+        }
+    }
+
+    #[test]
+    fn valid_box_is_kept() {
+        let coordinates = corners(10.0, 20.0, 30.0, 40.0);
+        let field = validate_field(raw(Some(2), coordinates), &pages());
+        assert!(field.warnings.is_empty());
+        assert_eq!(field.page, Some(2));
+        assert_eq!(field.bbox, Some(boxed(10.0, 20.0, 30.0, 40.0)));
+    }
+
+    #[test]
+    fn page_out_of_range_drops_location() {
+        for page in [0, -3, 3] {
+            let coordinates = corners(1.0, 1.0, 5.0, 5.0);
+            let field = validate_field(raw(Some(page), coordinates), &pages());
+            assert_eq!(field.page, None);
+            assert_eq!(field.bbox, None);
+            assert!(field.warnings[0].contains("outside the document"));
+        }
+    }
+
+    #[test]
+    fn swapped_corners_are_reordered() {
+        let coordinates = corners(30.0, 40.0, 10.0, 20.0);
+        let field = validate_field(raw(Some(1), coordinates), &pages());
+        assert_eq!(field.bbox, Some(boxed(10.0, 20.0, 30.0, 40.0)));
+        assert!(field.warnings[0].contains("swapped"));
+    }
+
+    #[test]
+    fn box_is_clamped_to_page() {
+        let coordinates = corners(-5.0, 790.0, 100.0, 900.0);
+        let field = validate_field(raw(Some(1), coordinates), &pages());
+        assert_eq!(field.bbox, Some(boxed(0.0, 790.0, 100.0, 800.0)));
+        assert!(field.warnings[0].contains("clamped"));
+    }
+
+    #[test]
+    fn box_off_the_page_is_dropped() {
+        let coordinates = corners(700.0, 10.0, 800.0, 20.0);
+        let field = validate_field(raw(Some(1), coordinates), &pages());
+        assert_eq!(field.page, Some(1));
+        assert_eq!(field.bbox, None);
+        assert!(field.warnings[0].contains("outside the page"));
+    }
+
+    #[test]
+    fn null_and_placeholder_boxes_are_silent() {
+        let field = validate_field(raw(None, [None; 4]), &pages());
+        assert_eq!(field.bbox, None);
+        assert!(field.warnings.is_empty());
+
+        let zero_field =
+            validate_field(raw(Some(1), corners(0.0, 0.0, 0.0, 0.0)), &pages());
+        assert_eq!(zero_field.bbox, None);
+        assert!(zero_field.warnings.is_empty());
+    }
+
+    #[test]
+    fn partial_box_is_dropped() {
+        let mut coordinates = corners(1.0, 1.0, 5.0, 5.0);
+        coordinates[1] = None;
+        let field = validate_field(raw(Some(1), coordinates), &pages());
+        assert_eq!(field.bbox, None);
+        assert!(field.warnings[0].contains("incomplete"));
+    }
+
+    fn candidate(value: &str, page: i64, ymin: f64, ymax: f64) -> RawCandidate {
+        RawCandidate {
+            value: Value::from(value),
+            page: Some(page),
+            xmin: Some(10.0),
+            ymin: Some(ymin),
+            xmax: Some(50.0),
+            ymax: Some(ymax),
+            evidence: Some(format!("n = {value}")),
+        }
+    }
+
+    #[test]
+    fn ambiguous_answers_keep_every_candidate() {
+        let mut conflicting = raw(Some(1), corners(1.0, 1.0, 5.0, 5.0));
+        conflicting.match_type = "ambiguous".to_owned();
+        conflicting.candidates = vec![candidate("18", 2, 100.0, 110.0)];
+        let field = validate_field(conflicting, &pages());
+        let value = field.value.clone().unwrap();
+        let values: Vec<&Value> =
+            field.candidates.iter().map(|kept| &kept.value).collect();
+        assert_eq!(values, [&value, &Value::from("18")]);
+        assert_eq!(field.candidates[0].page, Some(1));
+        assert_eq!(field.candidates[1].summary(), "18 (page 2)");
+        assert_eq!(
+            field.candidates[1].bbox,
+            Some(boxed(10.0, 100.0, 50.0, 110.0))
+        );
+
+        let mut off_page = raw(None, [None; 4]);
+        off_page.candidates = vec![candidate("18", 9, 100.0, 110.0)];
+        let unlocated = validate_field(off_page, &pages());
+        assert_eq!(unlocated.candidates.len(), 2);
+        assert_eq!(unlocated.candidates[1].page, None);
+        assert!(unlocated.warnings[0].starts_with("candidate 1: page 9"));
+
+        let mut merged = ExtractionResult::new();
+        merge_field(&mut merged, "n".to_owned(), field);
+        let mut later = raw(None, [None; 4]);
+        later.candidates = vec![candidate("19", 2, 300.0, 310.0)];
+        merge_field(
+            &mut merged,
+            "n".to_owned(),
+            validate_field(later, &pages()),
+        );
+        assert_eq!(merged["n"].match_type, "ambiguous");
+        assert_eq!(merged["n"].candidates.len(), 3);
+    }
+
+    #[test]
+    fn merging_does_not_depend_on_arrival_order() {
+        let on_page = |page, value: &str| {
+            let mut answer = raw(Some(page), corners(1.0, 1.0, 5.0, 5.0));
+            answer.value = Some(Value::from(value));
+            validate_field(answer, &pages())
+        };
+        for answers in [
+            [on_page(1, "24"), on_page(2, "22")],
+            [on_page(2, "22"), on_page(1, "24")],
+        ] {
+            let mut merged = ExtractionResult::new();
+            for answer in answers {
+                merge_field(&mut merged, "n".to_owned(), answer);
+            }
+            assert_eq!(merged["n"].value, Some(Value::from("24")));
+            assert_eq!(merged["n"].page, Some(1));
+        }
+    }
+
+    #[test]
+    fn capped_candidates_keep_the_conflict() {
+        fn window(
+            value: &str,
+            page: i64,
+            ymin: f64,
+            ymax: f64,
+        ) -> ExtractedField {
+            let mut answer = raw(Some(page), corners(10.0, ymin, 50.0, ymax));
+            answer.value = Some(Value::from(value));
+            answer.candidates = vec![candidate(value, page, ymin, ymax)];
+            validate_field(answer, &pages())
+        }
+        let mut merged = ExtractionResult::new();
+        for answer in [
+            window("100", 1, 100.0, 110.0),
+            window("100", 2, 100.0, 110.0),
+            window("120", 2, 600.0, 610.0),
+        ] {
+            merge_field(&mut merged, "n".to_owned(), answer);
+        }
+        let mut field = merged.remove("n").unwrap();
+        field.choose_primary(PrimaryRule::Model);
+        assert_eq!(field.match_type, "ambiguous");
+
+        let mut capped = field.clone();
+        capped.limit_candidates(2);
+        let values: Vec<&Value> =
+            capped.candidates.iter().map(|kept| &kept.value).collect();
+        assert_eq!(values, [&Value::from("100"), &Value::from("120")]);
+        assert_eq!(capped.candidates[0].page, Some(1));
+
+        field.limit_candidates(1);
+        assert_eq!(field.candidates.len(), 2);
+    }
+
+    #[test]
+    fn primary_rule_picks_among_candidates() {
+        let mut answer = raw(Some(2), corners(10.0, 100.0, 50.0, 110.0));
+        answer.candidates = vec![
+            candidate("18", 1, 500.0, 510.0),
+            candidate("18", 2, 50.0, 60.0),
+        ];
+        let field = validate_field(answer, &pages());
+
+        let model_value = field.value.clone().unwrap();
+        let chosen = |rule| {
+            let mut primary = field.clone();
+            primary.choose_primary(rule);
+            assert_eq!(primary.match_type, "ambiguous");
+            (primary.value.unwrap(), primary.page)
+        };
+        assert_eq!(chosen(PrimaryRule::Model), (model_value.clone(), Some(2)));
+        assert_eq!(chosen(PrimaryRule::First), (Value::from("18"), Some(1)));
+        assert_eq!(chosen(PrimaryRule::Last), (model_value, Some(2)));
+        assert_eq!(
+            chosen(PrimaryRule::MostCommon),
+            (Value::from("18"), Some(1))
+        );
+
+        let mut agreeing = field;
+        agreeing.candidates.truncate(1);
+        agreeing.candidates[0].page = Some(1);
+        let agreed = agreeing.candidates[0].clone();
+        agreeing.candidates.push(agreed);
+        agreeing.choose_primary(PrimaryRule::Model);
+        assert_eq!(agreeing.match_type, "found");
+    }
+}
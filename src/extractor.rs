@@ -0,0 +1,1199 @@
+use crate::batching::{Batching, estimate_output_tokens, plan_batches};
+use crate::examples::Examples;
+use crate::extraction::{
+    BoundingBox, ExtractedField, ExtractionResult, PrimaryRule, RawExtraction,
+    merge_field, validate_field, value_text,
+};
+use crate::input::{InputMode, document_parts};
+use crate::pdf::{
+    PageSize, PdfView, build_views, load_pdf, page_count, page_sizes,
+    select_pages,
+};
+use crate::progress::{Events, ProgressEvent};
+use crate::prompt::{PromptTemplate, PromptVariables};
+use crate::ratelimit::{RateLimiter, RateLimits, estimate_request_tokens};
+use crate::schema::{Schema, SchemaField, build_json_schema};
+use crate::secret::ApiKey;
+use crate::text::{locate_text, page_runs};
+use crate::usage::Usage;
+use crate::{Extraction, Result};
+use core::result;
+use core::time::Duration;
+use lopdf::Document;
+use reqwest::{Client, StatusCode};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+
+/// Model requested when none is set on the builder.
+pub const DEFAULT_MODEL: &str = "openai/gpt-5-mini";
+
+/// Number of fields sent per request when none is set on the builder.
+pub const DEFAULT_BATCH_SIZE: usize = 20;
+
+/// Number of requests in flight at once when none is set on the builder.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+/// Resolution used to rasterize pages in images mode when none is set.
+pub const DEFAULT_DPI: u32 = 150;
+
+/// Retries per request when none is set on the builder.
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
+
+/// Wait before the first retry; each further retry waits twice as long.
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+const OPENROUTER_URL: &str = "https://openrouter.ai/api/v1";
+
+/// Where extraction requests are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Provider {
+    /// `OpenRouter`'s chat completions API.
+    OpenRouter,
+    /// Any server that speaks the `OpenAI` chat completions API, such as a
+    /// local model server, given by its base URL (ending before
+    /// `/chat/completions`).
+    OpenAiCompatible(String),
+}
+
+impl Provider {
+    #[expect(
+        clippy::pattern_type_mismatch,
+        reason = "binding the URL by reference is the intent"
+    )]
+    fn chat_completions_url(&self) -> String {
+        let base = match self {
+            Self::OpenRouter => OPENROUTER_URL,
+            Self::OpenAiCompatible(base_url) => base_url.as_str(),
+        };
+        format!("{}/chat/completions", base.trim_end_matches('/'))
+    }
+
+    /// The environment variable conventionally holding this provider's API
+    /// key: `ANTHROPIC_API_KEY` for Anthropic's OpenAI-compatible endpoint
+    /// and `OPENAI_API_KEY` for any other compatible server.
+    #[expect(
+        clippy::pattern_type_mismatch,
+        reason = "binding the URL by reference is the intent"
+    )]
+    #[must_use]
+    #[inline]
+    pub fn api_key_variable(&self) -> &'static str {
+        match self {
+            Self::OpenRouter => "OPENROUTER_API_KEY",
+            Self::OpenAiCompatible(base_url)
+                if base_url.contains("api.anthropic.com") =>
+            {
+                "ANTHROPIC_API_KEY"
+            }
+            Self::OpenAiCompatible(_) => "OPENAI_API_KEY",
+        }
+    }
+}
+
+/// Extracts the fields of a [`Schema`] from PDFs. Build one with
+/// [`Extractor::builder`] and reuse it for every paper.
+#[derive(Debug)]
+pub struct Extractor {
+    schema: Schema,
+    provider: Provider,
+    model: String,
+    api_key: Option<ApiKey>,
+    batching: Batching,
+    /// Batches of the fields without a condition, sent in the first pass.
+    batches: Vec<Vec<SchemaField>>,
+    /// Fields with a condition, sent in a second pass when it holds.
+    conditional: Vec<SchemaField>,
+    pages: Option<Vec<u32>>,
+    exclude_references: bool,
+    chunk_pages: Option<usize>,
+    input_mode: InputMode,
+    dpi: u32,
+    max_retries: u32,
+    prompt: Arc<PromptTemplate>,
+    domain_context: Arc<str>,
+    examples: Arc<Examples>,
+    primary_rule: PrimaryRule,
+    client: Client,
+    permits: Arc<Semaphore>,
+    limiter: Arc<RateLimiter>,
+}
+
+/// Configures an [`Extractor`].
+#[derive(Debug)]
+pub struct ExtractorBuilder {
+    schema: Schema,
+    provider: Provider,
+    model: String,
+    api_key: Option<ApiKey>,
+    batching: Batching,
+    concurrency: usize,
+    pages: Option<Vec<u32>>,
+    exclude_references: bool,
+    chunk_pages: Option<usize>,
+    input_mode: InputMode,
+    dpi: u32,
+    max_retries: u32,
+    rate_limits: RateLimits,
+    prompt: PromptTemplate,
+    domain_context: String,
+    examples: Examples,
+    primary_rule: PrimaryRule,
+}
+
+impl ExtractorBuilder {
+    #[must_use]
+    #[inline]
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.provider = provider;
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn model<S>(mut self, model: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.model = model.into();
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn api_key<K>(mut self, api_key: K) -> Self
+    where
+        K: Into<ApiKey>,
+    {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Number of schema fields sent in each request.
+    #[must_use]
+    #[inline]
+    pub const fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batching = Batching::Fields(batch_size);
+        self
+    }
+
+    /// How fields are grouped into requests. Fields sharing a `batch` key in
+    /// the schema always go together.
+    #[must_use]
+    #[inline]
+    pub const fn batching(mut self, batching: Batching) -> Self {
+        self.batching = batching;
+        self
+    }
+
+    /// Maximum number of requests in flight at once. The first request for
+    /// each page window is sent on its own, so the others can reuse the
+    /// document from the provider's prompt cache.
+    #[must_use]
+    #[inline]
+    pub const fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+
+    /// Only send these pages (1-based).
+    #[must_use]
+    #[inline]
+    pub fn pages(mut self, pages: Vec<u32>) -> Self {
+        self.pages = Some(pages);
+        self
+    }
+
+    /// Drop the reference list and anything after it.
+    #[must_use]
+    #[inline]
+    pub const fn exclude_references(mut self, exclude: bool) -> Self {
+        self.exclude_references = exclude;
+        self
+    }
+
+    /// Split the document into windows of this many pages.
+    #[must_use]
+    #[inline]
+    pub const fn chunk_pages(mut self, pages: usize) -> Self {
+        self.chunk_pages = Some(pages);
+        self
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn input_mode(mut self, input_mode: InputMode) -> Self {
+        self.input_mode = input_mode;
+        self
+    }
+
+    /// Resolution used to rasterize pages in [`InputMode::Images`].
+    #[must_use]
+    #[inline]
+    pub const fn dpi(mut self, dpi: u32) -> Self {
+        self.dpi = dpi;
+        self
+    }
+
+    /// How often a request is retried after a network error, a rate limit
+    /// or a server error, waiting twice as long each time.
+    #[must_use]
+    #[inline]
+    pub const fn max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Requests and tokens per minute allowed by the provider. Every paper
+    /// extracted with this extractor shares the same allowance.
+    #[must_use]
+    #[inline]
+    pub const fn rate_limits(mut self, limits: RateLimits) -> Self {
+        self.rate_limits = limits;
+        self
+    }
+
+    /// Instructions sent with every request, replacing the built-in prompt.
+    #[must_use]
+    #[inline]
+    pub fn prompt_template(mut self, template: PromptTemplate) -> Self {
+        self.prompt = template;
+        self
+    }
+
+    /// Background on the research area, filled in for the template's
+    /// `{{DOMAIN_CONTEXT}}`.
+    #[must_use]
+    #[inline]
+    pub fn domain_context<S>(mut self, context: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.domain_context = context.into();
+        self
+    }
+
+    /// Reviewed answers from other papers to list under each field.
+    #[must_use]
+    #[inline]
+    pub fn examples(mut self, examples: Examples) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    /// Which candidate becomes a field's value when the paper reports
+    /// several.
+    #[must_use]
+    #[inline]
+    pub const fn primary_rule(mut self, rule: PrimaryRule) -> Self {
+        self.primary_rule = rule;
+        self
+    }
+
+    /// Checks the settings and creates the extractor.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the batch size, concurrency, page window, DPI or a rate
+    /// limit is zero, or when `OpenRouter` is used without an API key.
+    #[inline]
+    pub fn build(self) -> Result<Extractor> {
+        if matches!(
+            self.batching,
+            Batching::Fields(0) | Batching::OutputTokens(0)
+        ) {
+            return Err("Batch size must be at least 1".to_owned());
+        }
+        if self.concurrency == 0 {
+            return Err("Concurrency must be at least 1".to_owned());
+        }
+        if self.chunk_pages == Some(0) {
+            return Err("Page windows must hold at least 1 page".to_owned());
+        }
+        if self.dpi == 0 {
+            return Err("DPI must be at least 1".to_owned());
+        }
+        if self.rate_limits.requests_per_minute == Some(0)
+            || self.rate_limits.tokens_per_minute == Some(0)
+        {
+            return Err("Rate limits must be at least 1 per minute".to_owned());
+        }
+        if self.provider == Provider::OpenRouter && self.api_key.is_none() {
+            return Err("An API key is required for OpenRouter".to_owned());
+        }
+
+        let (conditional, unconditional): (Vec<_>, Vec<_>) = self
+            .schema
+            .fields()
+            .iter()
+            .cloned()
+            .partition(|field| field.when.is_some());
+        let batches = plan_batches(&unconditional, self.batching);
+        Ok(Extractor {
+            schema: self.schema,
+            provider: self.provider,
+            model: self.model,
+            api_key: self.api_key,
+            batching: self.batching,
+            batches,
+            conditional,
+            pages: self.pages,
+            exclude_references: self.exclude_references,
+            chunk_pages: self.chunk_pages,
+            input_mode: self.input_mode,
+            dpi: self.dpi,
+            max_retries: self.max_retries,
+            prompt: Arc::new(self.prompt),
+            domain_context: Arc::from(self.domain_context),
+            examples: Arc::new(self.examples),
+            primary_rule: self.primary_rule,
+            client: Client::new(),
+            permits: Arc::new(Semaphore::new(self.concurrency)),
+            limiter: Arc::new(RateLimiter::new(self.rate_limits)),
+        })
+    }
+}
+
+/// Everything a spawned request needs, shared between the tasks of one
+/// paper.
+struct RequestContext {
+    url: String,
+    model: String,
+    api_key: Option<ApiKey>,
+    client: Client,
+    permits: Arc<Semaphore>,
+    limiter: Arc<RateLimiter>,
+    max_retries: u32,
+    prompt: Arc<PromptTemplate>,
+    domain_context: Arc<str>,
+    examples: Arc<Examples>,
+    filename: String,
+    batch_count: usize,
+    events: Events,
+}
+
+impl RequestContext {
+    /// Removes the API key from an error that may quote the raw response.
+    fn redact(&self, text: &str) -> String {
+        self.api_key
+            .as_ref()
+            .map_or_else(|| text.to_owned(), |key| key.redact(text))
+    }
+}
+
+impl Extractor {
+    /// Starts configuring an extractor for `schema`, defaulting to
+    /// `OpenRouter` and [`DEFAULT_MODEL`].
+    #[must_use]
+    #[inline]
+    pub fn builder(schema: Schema) -> ExtractorBuilder {
+        ExtractorBuilder {
+            schema,
+            provider: Provider::OpenRouter,
+            model: DEFAULT_MODEL.to_owned(),
+            api_key: None,
+            batching: Batching::Fields(DEFAULT_BATCH_SIZE),
+            concurrency: DEFAULT_CONCURRENCY,
+            pages: None,
+            exclude_references: false,
+            chunk_pages: None,
+            input_mode: InputMode::Pdf,
+            dpi: DEFAULT_DPI,
+            max_retries: DEFAULT_MAX_RETRIES,
+            rate_limits: RateLimits::default(),
+            prompt: PromptTemplate::default(),
+            domain_context: String::new(),
+            examples: Examples::default(),
+            primary_rule: PrimaryRule::default(),
+        }
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    #[must_use]
+    #[inline]
+    pub const fn input_mode(&self) -> InputMode {
+        self.input_mode
+    }
+
+    /// Hash of the prompt template and domain context, identifying the
+    /// instructions used.
+    #[must_use]
+    #[inline]
+    pub fn prompt_hash(&self) -> String {
+        self.prompt.hash(&self.domain_context)
+    }
+
+    /// Extracts every schema field from a PDF.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the PDF cannot be read, a request fails or the model's
+    /// answer cannot be parsed.
+    #[inline]
+    pub async fn extract(&self, pdf: &[u8]) -> Result<Extraction> {
+        self.run(pdf, "", Events::default()).await
+    }
+
+    /// Like [`Extractor::extract`], sending a [`ProgressEvent`] to `events`
+    /// as the work goes on. The channel closes when extraction finishes, so
+    /// the receiver can be drained alongside the extraction future.
+    /// `filename` is the prompt template's `{{FILENAME}}`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the PDF cannot be read, a request fails or the model's
+    /// answer cannot be parsed.
+    #[inline]
+    pub async fn extract_with_events(
+        &self,
+        pdf: &[u8],
+        filename: &str,
+        events: UnboundedSender<ProgressEvent>,
+    ) -> Result<Extraction> {
+        self.run(pdf, filename, Events::new(events)).await
+    }
+
+    async fn run(
+        &self,
+        pdf: &[u8],
+        filename: &str,
+        events: Events,
+    ) -> Result<Extraction> {
+        let document = load_pdf(pdf)?;
+        let selected = select_pages(
+            &document,
+            self.pages.as_deref(),
+            self.exclude_references,
+        )?;
+        let views = build_views(pdf, &document, &selected, self.chunk_pages)?;
+        events.emit(ProgressEvent::PaperStarted {
+            page_count: page_count(&document),
+            pages_sent: selected.len(),
+            windows: views.len(),
+            batches: self.batches.len().saturating_mul(views.len()),
+        });
+        let parts = views
+            .iter()
+            .map(|view| {
+                document_parts(self.input_mode, &document, view, self.dpi)
+                    .map(Arc::from)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let paper = Paper {
+            sizes: page_sizes(&document),
+            views,
+            parts,
+            filename,
+            events,
+        };
+
+        let mut merged = BTreeMap::new();
+        let mut usage = Usage::default();
+        self.run_pass(&paper, &self.batches, 0, &mut merged, &mut usage)
+            .await?;
+        if !self.conditional.is_empty() {
+            let second = self.plan_conditional(&paper, &mut merged);
+            self.run_pass(
+                &paper,
+                &second,
+                self.batches.len(),
+                &mut merged,
+                &mut usage,
+            )
+            .await?;
+        }
+
+        if self.input_mode == InputMode::Text {
+            locate_text_boxes(&mut merged, &document);
+        }
+        for field in merged.values_mut() {
+            field.choose_primary(self.primary_rule);
+        }
+        limit_candidates(&mut merged, self.schema.fields());
+
+        paper.events.emit(ProgressEvent::paper_done(&merged, usage));
+        Ok(merged)
+    }
+
+    /// Sends `batches` with every page window and merges the answers into
+    /// `merged`. Batches are numbered from `first_batch`, so the second pass
+    /// carries on from the first.
+    async fn run_pass(
+        &self,
+        paper: &Paper<'_>,
+        batches: &[Vec<SchemaField>],
+        first_batch: usize,
+        merged: &mut ExtractionResult,
+        usage: &mut Usage,
+    ) -> Result<()> {
+        let mut tasks = self.spawn_requests(paper, batches, first_batch);
+
+        // Merge each answer as it arrives. After a failure the requests not
+        // yet answered are cancelled, but answers that already arrived are
+        // still merged and reported, so a partial run is kept.
+        let image_dpi =
+            (self.input_mode == InputMode::Images).then_some(self.dpi);
+        let mut failure = None;
+        while let Some(joined) = tasks.join_next().await {
+            let answer = match joined
+                .map_err(|e| format!("Task failed: {e}"))
+                .and_then(|result| result)
+            {
+                Ok(answer) => answer,
+                Err(e) => {
+                    tasks.abort_all();
+                    failure.get_or_insert(e);
+                    continue;
+                }
+            };
+
+            let names: Vec<String> = answer.fields.keys().cloned().collect();
+            merge_window_results(
+                merged,
+                answer.fields,
+                &paper.views[answer.window],
+                &paper.sizes,
+                image_dpi,
+                &paper.events,
+            );
+            let results = names
+                .iter()
+                .filter_map(|name| merged.get_key_value(name))
+                .map(|(name, field)| (name.clone(), field.clone()))
+                .collect();
+            usage.include(answer.usage);
+            paper.events.emit(ProgressEvent::BatchCompleted {
+                window: answer.window,
+                batch: answer.batch,
+                fields: names,
+                usage: answer.usage,
+                results,
+            });
+        }
+        failure.map_or(Ok(()), Err)
+    }
+
+    /// Checks each conditional field's condition against the first pass.
+    /// Fields whose condition fails are recorded as `not_applicable`; the
+    /// rest are planned into batches for the second pass. A condition is only
+    /// decided by a `found` or `inferred` answer, so fields testing a field
+    /// that was not found or is ambiguous are extracted too.
+    fn plan_conditional(
+        &self,
+        paper: &Paper<'_>,
+        merged: &mut ExtractionResult,
+    ) -> Vec<Vec<SchemaField>> {
+        let mut applicable = Vec::new();
+        let conditions = self.conditional.iter().filter_map(|field| {
+            field.when.as_ref().map(|condition| (field, condition))
+        });
+        for (field, condition) in conditions {
+            let answer = merged
+                .get(&condition.field)
+                .filter(|tested| {
+                    matches!(tested.match_type.as_str(), "found" | "inferred")
+                })
+                .map(|tested| {
+                    tested.value.as_ref().map(value_text).unwrap_or_default()
+                });
+            if answer.is_none_or(|text| condition.holds(&text)) {
+                applicable.push(field.clone());
+            } else {
+                merged.insert(
+                    field.field_name.clone(),
+                    ExtractedField::not_applicable(condition),
+                );
+            }
+        }
+
+        let batches = plan_batches(&applicable, self.batching);
+        paper.events.emit(ProgressEvent::ConditionalPass {
+            applicable: applicable.len(),
+            not_applicable: self
+                .conditional
+                .len()
+                .saturating_sub(applicable.len()),
+            batches: batches.len().saturating_mul(paper.views.len()),
+        });
+        batches
+    }
+
+    /// Spawns one request per batch of fields per page window. Requests
+    /// wait for a permit, so at most `concurrency` are in flight.
+    ///
+    /// The first request of each window goes out on its own and the rest of
+    /// the window waits for it to finish, so the provider has cached the
+    /// document by the time the other requests reuse it.
+    fn spawn_requests(
+        &self,
+        paper: &Paper<'_>,
+        batches: &[Vec<SchemaField>],
+        first_batch: usize,
+    ) -> JoinSet<BatchResult> {
+        let context = Arc::new(RequestContext {
+            url: self.provider.chat_completions_url(),
+            model: self.model.clone(),
+            api_key: self.api_key.clone(),
+            client: self.client.clone(),
+            permits: Arc::clone(&self.permits),
+            limiter: Arc::clone(&self.limiter),
+            max_retries: self.max_retries,
+            prompt: Arc::clone(&self.prompt),
+            domain_context: Arc::clone(&self.domain_context),
+            examples: Arc::clone(&self.examples),
+            filename: paper.filename.to_owned(),
+            batch_count: first_batch.saturating_add(batches.len()),
+            events: paper.events.clone(),
+        });
+        let mut tasks = JoinSet::new();
+
+        for (window, (view, parts)) in
+            paper.views.iter().zip(&paper.parts).enumerate()
+        {
+            let (cached, cache_ready) = watch::channel(());
+            let mut first = Some(cached);
+            for (index, fields) in batches.iter().enumerate() {
+                // Only the first request holds the sender, which is dropped
+                // when it finishes.
+                let leader = first.take();
+                let mut ready = cache_ready.clone();
+                let context_clone = Arc::clone(&context);
+                let batch = first_batch.saturating_add(index);
+                let request = BatchRequest {
+                    window,
+                    batch,
+                    pages: view.pages.len(),
+                    fields: fields.clone(),
+                    parts: Arc::clone(parts),
+                };
+
+                tasks.spawn(async move {
+                    if leader.is_none() {
+                        // Nothing is ever sent, so this ends once the first
+                        // request of the window has finished.
+                        while ready.changed().await.is_ok() {}
+                    }
+                    let _permit = context_clone
+                        .permits
+                        .acquire()
+                        .await
+                        .map_err(|e| e.to_string())?;
+                    context_clone.events.emit(ProgressEvent::BatchStarted {
+                        window,
+                        batch,
+                        fields: request.fields.len(),
+                    });
+                    let response =
+                        send_with_retries(&context_clone, &request).await?;
+                    Ok(BatchAnswer {
+                        window,
+                        batch,
+                        usage: Usage::from_response(&response),
+                        fields: parse_extraction(&response)
+                            .map_err(|e| context_clone.redact(&e))?,
+                    })
+                });
+            }
+        }
+
+        tasks
+    }
+}
+
+/// One paper's page windows, prepared once and sent in every pass.
+struct Paper<'a> {
+    views: Vec<PdfView>,
+    /// The message parts of each window, shared by all of its requests.
+    parts: Vec<Arc<[Value]>>,
+    sizes: BTreeMap<u32, PageSize>,
+    filename: &'a str,
+    events: Events,
+}
+
+/// What one request came back with.
+struct BatchAnswer {
+    window: usize,
+    batch: usize,
+    fields: RawExtraction,
+    usage: Usage,
+}
+
+type BatchResult = Result<BatchAnswer>;
+
+fn parse_extraction(response: &Value) -> Result<RawExtraction> {
+    // Extract results from response
+    let content = response
+        .get("choices")
+        .and_then(|choices| choices.get(0))
+        .and_then(|choice| choice.get("message"))
+        .and_then(|message| message.get("content"))
+        .ok_or_else(|| {
+            format!(
+                "Failed to extract content from API response: {}",
+                serde_json::to_string_pretty(response)
+                    .unwrap_or_else(|_| "<failed to serialize>".to_owned())
+            )
+        })?;
+
+    let content_str = content.as_str().ok_or_else(|| {
+        format!("Expected string content in response, got: {content}")
+    })?;
+
+    serde_json::from_str(content_str).map_err(|e| {
+        format!(
+            "Failed to parse extracted data: {e}\nRaw content: {content_str}"
+        )
+    })
+}
+
+/// Maps each field of one page window back to original page numbers,
+/// validates its location and merges it into the overall result.
+/// `image_dpi` is set when boxes were measured on rendered page images.
+///
+/// A field that keeps its box after validation only had that box corrected,
+/// so its warnings are reported as repairs; otherwise part of the location
+/// was dropped and they are reported as validation warnings.
+fn merge_window_results(
+    merged: &mut ExtractionResult,
+    window_results: RawExtraction,
+    view: &PdfView,
+    sizes: &BTreeMap<u32, PageSize>,
+    image_dpi: Option<u32>,
+    events: &Events,
+) {
+    for (field_name, mut raw) in window_results {
+        raw.map_pages(|page| view.original_page(page));
+        if let Some(dpi) = image_dpi {
+            raw.pixels_to_points(dpi);
+        }
+
+        let field = validate_field(raw, sizes);
+        for warning in &field.warnings {
+            events.emit(if field.bbox.is_some() {
+                ProgressEvent::FieldRepaired {
+                    field: field_name.clone(),
+                    repair: warning.clone(),
+                }
+            } else {
+                ProgressEvent::ValidationWarning {
+                    field: field_name.clone(),
+                    warning: warning.clone(),
+                }
+            });
+        }
+        merge_field(merged, field_name, field);
+    }
+}
+
+/// Caps each field at its `max_candidates`. The response format limits
+/// every answer, but merging the answers of several page windows and passes
+/// can add up to more. Runs after the value is chosen, so the choice and the
+/// conflict check see every candidate.
+fn limit_candidates(results: &mut ExtractionResult, fields: &[SchemaField]) {
+    for field in fields {
+        if let (Some(limit), Some(result)) =
+            (field.max_candidates, results.get_mut(&field.field_name))
+        {
+            result.limit_candidates(limit);
+        }
+    }
+}
+
+/// In text mode the model cannot see coordinates, so found values and
+/// candidates are looked up in the page's text runs to recover a bounding
+/// box.
+fn locate_text_boxes(results: &mut ExtractionResult, document: &Document) {
+    let mut runs_by_page = BTreeMap::new();
+    let mut locate = |page: Option<u32>, value: &Value| {
+        let page_number = page?;
+        let runs = runs_by_page
+            .entry(page_number)
+            .or_insert_with(|| page_runs(document, page_number).ok());
+        let run = locate_text(runs.as_deref()?, &value_text(value))?;
+        Some(BoundingBox {
+            xmin: run.xmin,
+            ymin: run.ymin,
+            xmax: run.xmax,
+            ymax: run.ymax,
+        })
+    };
+    for field in results.values_mut() {
+        if let Some(value) = field.value.as_ref()
+            && let Some(bbox) = locate(field.page, value)
+        {
+            field.bbox = Some(bbox);
+        }
+        for candidate in &mut field.candidates {
+            if let Some(bbox) = locate(candidate.page, &candidate.value) {
+                candidate.bbox = Some(bbox);
+            }
+        }
+    }
+}
+
+/// Why a request failed. Network errors, rate limits and server errors may
+/// pass, so they are retried; anything else is reported straight away.
+enum RequestError {
+    Transient(String),
+    Fatal(String),
+}
+
+/// One batch of fields sent with one page window. The (window, batch) pair
+/// identifies it in events.
+struct BatchRequest {
+    window: usize,
+    batch: usize,
+    pages: usize,
+    fields: Vec<SchemaField>,
+    parts: Arc<[Value]>,
+}
+
+/// Sends one batch, retrying transient failures up to `max_retries` times
+/// with a doubling delay. Every attempt first waits for the rate limiter.
+async fn send_with_retries(
+    settings: &RequestContext,
+    request: &BatchRequest,
+) -> Result<Value> {
+    let prompt = settings.prompt.render(&PromptVariables {
+        fields: &request.fields,
+        filename: &settings.filename,
+        domain_context: &settings.domain_context,
+        batch_index: request.batch.saturating_add(1),
+        batch_count: settings.batch_count,
+        examples: &settings
+            .examples
+            .select(&request.fields, &settings.filename),
+    });
+    let tokens = estimate_request_tokens(
+        &prompt,
+        &request.parts,
+        request.pages,
+        request.fields.iter().map(estimate_output_tokens).sum(),
+    );
+    let body = request_body(&settings.model, &prompt, request);
+
+    let mut delay = RETRY_DELAY;
+    let mut attempt: u32 = 0;
+    loop {
+        settings.limiter.acquire(tokens).await;
+        match call_chat_completions(settings, &body).await {
+            Ok(response) => return Ok(response),
+            Err(RequestError::Fatal(error)) => return Err(error),
+            Err(RequestError::Transient(error)) => {
+                if attempt >= settings.max_retries {
+                    return Err(error);
+                }
+                attempt = attempt.saturating_add(1);
+                settings.events.emit(ProgressEvent::Retry {
+                    window: request.window,
+                    batch: request.batch,
+                    attempt,
+                    error,
+                });
+                sleep(delay).await;
+                delay = delay.saturating_mul(2);
+            }
+        }
+    }
+}
+
+/// Whether the model needs explicit `cache_control` markers to cache a
+/// prompt prefix. `OpenAI` models cache long prefixes automatically.
+fn uses_cache_control(model: &str) -> bool {
+    model.starts_with("anthropic/") || model.contains("claude")
+}
+
+/// Builds the request with the document first and the field instructions
+/// last, so every batch of a page window shares the same prefix and the
+/// provider can serve the document from its prompt cache.
+fn request_body(model: &str, prompt: &str, request: &BatchRequest) -> Value {
+    let json_schema = build_json_schema(&request.fields);
+
+    let mut content = request.parts.to_vec();
+    if uses_cache_control(model)
+        && let Some(last) = content.last_mut().and_then(Value::as_object_mut)
+    {
+        last.insert("cache_control".to_owned(), json!({"type": "ephemeral"}));
+    }
+    content.push(json!({
+        "type": "text",
+        "text": prompt
+    }));
+
+    json!({
+        "model": model,
+        "messages": [
+            {
+                "role": "user",
+                "content": content
+            }
+        ],
+        "response_format": {
+            "type": "json_schema",
+            "json_schema": {
+                "name": "extraction",
+                "strict": true,
+                "schema": json_schema
+            }
+        }
+    })
+}
+
+async fn call_chat_completions(
+    settings: &RequestContext,
+    request_body: &Value,
+) -> result::Result<Value, RequestError> {
+    let mut request = settings
+        .client
+        .post(&settings.url)
+        .header("Content-Type", "application/json");
+    if let Some(api_key) = settings.api_key.as_ref() {
+        request = request
+            .header("Authorization", format!("Bearer {}", api_key.expose()));
+    }
+    let response = request.json(request_body).send().await.map_err(|e| {
+        RequestError::Transient(format!("Failed to send request: {e}"))
+    })?;
+
+    let status = response.status();
+    let response_text = response.text().await.map_err(|e| {
+        RequestError::Transient(format!("Failed to read response: {e}"))
+    })?;
+    let transient =
+        status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+
+    // Parse the response JSON
+    let response_json: Value =
+        serde_json::from_str(&response_text).map_err(|e| {
+            let message = settings.redact(&format!(
+                "Invalid JSON response from API ({status}): {e}\nRaw response: {response_text}"
+            ));
+            if transient {
+                RequestError::Transient(message)
+            } else {
+                RequestError::Fatal(message)
+            }
+        })?;
+
+    // Check if the response indicates an error
+    if !status.is_success() || response_json.get("error").is_some() {
+        let message = response_json
+            .get("error")
+            .and_then(|error| error.get("message"))
+            .and_then(Value::as_str)
+            .map_or_else(
+                || format!("API request failed with status: {status}"),
+                |message| {
+                    settings.redact(&format!("API error ({status}): {message}"))
+                },
+            );
+        return Err(if transient {
+            RequestError::Transient(message)
+        } else {
+            RequestError::Fatal(message)
+        });
+    }
+
+    Ok(response_json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::Candidate;
+    use crate::schema::SchemaKind;
+
+    fn schema() -> Schema {
+        Schema::parse(
+            "field_name,description,kind,infer\ntitle,Paper title,text,false",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn builder_checks_settings() {
+        let missing_key = Extractor::builder(schema()).build();
+        assert!(missing_key.unwrap_err().contains("API key"));
+
+        let no_batch = Extractor::builder(schema())
+            .api_key("key")
+            .batch_size(0)
+            .build();
+        assert!(no_batch.unwrap_err().contains("Batch size"));
+
+        let local = Extractor::builder(schema())
+            .provider(Provider::OpenAiCompatible(
+                "http://localhost:8080/v1/".to_owned(),
+            ))
+            .model("local")
+            .build()
+            .unwrap();
+        assert_eq!(local.model(), "local");
+        assert_eq!(
+            local.provider.chat_completions_url(),
+            "http://localhost:8080/v1/chat/completions"
+        );
+    }
+
+    #[test]
+    fn conditional_fields_wait_for_the_fields_they_test() {
+        let schema = Schema::parse(
+            "field_name,description,kind,infer,when\n\
+             arm,Intervention arm,text,false,\n\
+             graft_volume,Graft volume,number,false,\"arm != \"\"No graft\"\"\"\n\
+             graft_site,Harvest site,text,false,\"arm != \"\"No graft\"\"\"",
+        )
+        .unwrap();
+        let extractor =
+            Extractor::builder(schema).api_key("key").build().unwrap();
+        assert_eq!(extractor.batches.len(), 1);
+        assert_eq!(extractor.conditional.len(), 2);
+
+        let paper = Paper {
+            views: Vec::new(),
+            parts: Vec::new(),
+            sizes: BTreeMap::new(),
+            filename: "",
+            events: Events::default(),
+        };
+        let arm = |value: &str, match_type: &str| ExtractedField {
+            value: Some(Value::from(value)),
+            match_type: match_type.to_owned(),
+            comment: None,
+            page: None,
+            bbox: None,
+            warnings: Vec::new(),
+            candidates: Vec::new(),
+        };
+
+        let mut grafted = ExtractionResult::from([(
+            "arm".to_owned(),
+            arm("Autograft", "found"),
+        )]);
+        let second = extractor.plan_conditional(&paper, &mut grafted);
+        assert_eq!(second.iter().map(Vec::len).sum::<usize>(), 2);
+        assert_eq!(grafted.len(), 1);
+
+        let mut no_graft = ExtractionResult::from([(
+            "arm".to_owned(),
+            arm("No graft", "found"),
+        )]);
+        let skipped = extractor.plan_conditional(&paper, &mut no_graft);
+        assert!(skipped.is_empty());
+        assert_eq!(no_graft["graft_site"].match_type, "not_applicable");
+        assert_eq!(
+            no_graft["graft_volume"].comment.as_deref(),
+            Some("Not applicable: condition arm != \"No graft\" does not hold")
+        );
+
+        for match_type in ["not_found", "ambiguous"] {
+            let mut unknown = ExtractionResult::from([(
+                "arm".to_owned(),
+                arm("No graft", match_type),
+            )]);
+            let asked = extractor.plan_conditional(&paper, &mut unknown);
+            assert_eq!(asked.iter().map(Vec::len).sum::<usize>(), 2);
+            assert_eq!(unknown.len(), 1);
+        }
+        let mut missing = ExtractionResult::new();
+        let asked = extractor.plan_conditional(&paper, &mut missing);
+        assert_eq!(asked.iter().map(Vec::len).sum::<usize>(), 2);
+    }
+
+    #[test]
+    fn document_comes_before_the_field_instructions() {
+        let request = BatchRequest {
+            window: 0,
+            batch: 0,
+            pages: 1,
+            fields: schema().fields().to_vec(),
+            parts: Arc::from(vec![json!({"type": "file", "file": {}})]),
+        };
+
+        let body =
+            request_body("anthropic/claude-sonnet-4", "prompt", &request);
+        let content = &body["messages"][0]["content"];
+        assert_eq!(content[0]["type"], "file");
+        assert_eq!(content[0]["cache_control"]["type"], "ephemeral");
+        assert_eq!(content[1]["text"], "prompt");
+
+        let uncached = request_body(DEFAULT_MODEL, "prompt", &request);
+        assert!(
+            uncached["messages"][0]["content"][0]
+                .get("cache_control")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn merged_windows_keep_the_candidate_limit() {
+        let mut n = SchemaField::new("n", SchemaKind::Number);
+        n.max_candidates = Some(3);
+        let answer = |pages: [u32; 2]| ExtractedField {
+            value: Some(Value::from(pages[0])),
+            match_type: "found".to_owned(),
+            comment: None,
+            page: Some(pages[0]),
+            bbox: None,
+            warnings: Vec::new(),
+            candidates: pages
+                .map(|page| Candidate {
+                    value: Value::from(page),
+                    page: Some(page),
+                    bbox: None,
+                    evidence: None,
+                })
+                .to_vec(),
+        };
+
+        let mut merged = ExtractionResult::new();
+        merge_field(&mut merged, "n".to_owned(), answer([5, 6]));
+        merge_field(&mut merged, "n".to_owned(), answer([1, 2]));
+        assert_eq!(merged["n"].candidates.len(), 4);
+
+        let field = merged.get_mut("n").unwrap();
+        field.choose_primary(PrimaryRule::Last);
+        assert_eq!(field.page, Some(6));
+
+        limit_candidates(&mut merged, &[n]);
+        let pages: Vec<Option<u32>> = merged["n"]
+            .candidates
+            .iter()
+            .map(|candidate| candidate.page)
+            .collect();
+        assert_eq!(pages, [Some(1), Some(2), Some(6)]);
+    }
+
+    #[test]
+    fn parse_extraction_reports_bad_content() {
+        let response = json!({
+            "choices": [{"message": {"content": "{\"title\": {\"value\": \"A\", \"match_type\": \"found\", \"comment\": null, \"page\": 1, \"xmin\": null, \"ymin\": null, \"xmax\": null, \"ymax\": null}}"}}]
+        });
+        let parsed = parse_extraction(&response).unwrap();
+        assert_eq!(parsed["title"].match_type, "found");
+
+        let truncated = json!({"choices": [{"message": {"content": "{"}}]});
+        assert!(
+            parse_extraction(&truncated)
+                .unwrap_err()
+                .contains("Failed to parse extracted data")
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use crate::pdf::{PdfView, pdf_to_base64};
+use crate::render::render_page_png;
+use crate::text::document_text;
+use base64::{Engine as _, engine::general_purpose};
+use clap::ValueEnum;
+use lopdf::Document;
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+/// Preamble sent before the document text in text input mode.
+const TEXT_PREAMBLE: &str = "The document is provided as extracted text. \
+Each page starts with a `--- Page N ---` marker; report `page` using that N. \
+Coordinates are not available in this mode, so set xmin, ymin, xmax and ymax \
+to null.";
+
+/// Preamble sent before the page images in images input mode.
+const IMAGES_PREAMBLE: &str = "The document is provided as one image per \
+page. Each image is preceded by a `--- Page N ---` label; report `page` using \
+that N. Give xmin, ymin, xmax and ymax in pixels of that page image, with the \
+origin at its top-left corner.";
+
+/// How the document is handed to the model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum InputMode {
+    /// Send the PDF as a file part and let the provider parse it.
+    Pdf,
+    /// Extract the text locally and send it as plain text.
+    Text,
+    /// Rasterize each page locally and send PNG images to a vision model.
+    Images,
+}
+
+/// Builds the message content parts that carry one page window of the
+/// document.
+pub fn document_parts(
+    mode: InputMode,
+    document: &Document,
+    view: &PdfView,
+    dpi: u32,
+) -> Result<Vec<Value>, String> {
+    match mode {
+        InputMode::Pdf => Ok(vec![json!({
+            "type": "file",
+            "file": {
+                "filename": "document.pdf",
+                "file_data": pdf_to_base64(&view.data),
+            }
+        })]),
+        InputMode::Text => Ok(vec![json!({
+            "type": "text",
+            "text": format!(
+                "{TEXT_PREAMBLE}\n\n{}",
+                document_text(document, &view.pages)?
+            ),
+        })]),
+        InputMode::Images => image_parts(view, dpi),
+    }
+}
+
+fn image_parts(view: &PdfView, dpi: u32) -> Result<Vec<Value>, String> {
+    let mut parts = vec![json!({
+        "type": "text",
+        "text": IMAGES_PREAMBLE,
+    })];
+
+    for page in (1..).take(view.pages.len()) {
+        let png = render_page_png(&view.data, page, dpi)?;
+        let png_base64 = general_purpose::STANDARD.encode(png);
+        parts.push(json!({
+            "type": "text",
+            "text": format!("--- Page {page} ---"),
+        }));
+        parts.push(json!({
+            "type": "image_url",
+            "image_url": {
+                "url": format!("data:image/png;base64,{png_base64}"),
+            }
+        }));
+    }
+
+    Ok(parts)
+}
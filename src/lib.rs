@@ -0,0 +1,94 @@
+//! Extracts structured data from scientific papers with a language model.
+//!
+//! A [`Schema`] lists the fields to extract; an [`Extractor`] sends a PDF to
+//! the model in batches of fields and merges the answers, each with its match
+//! type, page and location in the paper.
+//!
+//! ```no_run
+//! use paper_extract::{Extractor, Schema};
+//!
+//! # async fn run() -> paper_extract::Result<()> {
+//! let schema = Schema::from_path("schema.csv")?;
+//! let extractor = Extractor::builder(schema)
+//!     .api_key("sk-...")
+//!     .batch_size(10)
+//!     .concurrency(4)
+//!     .build()?;
+//!
+//! let pdf = std::fs::read("paper.pdf").map_err(|e| e.to_string())?;
+//! let extraction = extractor.extract(&pdf).await?;
+//! for (field, extracted) in &extraction {
+//!     println!("{field}: {:?} ({})", extracted.value, extracted.match_type);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The `SQLite` sink, the Excel writer and the review screen sit behind the
+//! `sqlite`, `xlsx` and `review` features. The default `cli` feature turns
+//! them all on for the command-line tool.
+
+pub mod annotate;
+mod batching;
+mod config;
+mod examples;
+mod extraction;
+mod extractor;
+mod input;
+pub mod output;
+pub mod pdf;
+mod progress;
+mod prompt;
+mod ratelimit;
+mod render;
+pub mod report;
+#[cfg(feature = "review")]
+pub mod review;
+pub mod risk_of_bias;
+mod schema;
+pub mod screening;
+mod secret;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(test)]
+mod testing;
+mod text;
+mod usage;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
+
+use core::result;
+
+#[expect(
+    clippy::pub_use,
+    clippy::useless_attribute,
+    reason = "the modules stay private and this is the public API of the \
+              crate; clippy does not know that `pub_use` is checked on the \
+              `use` item itself"
+)]
+pub use crate::{
+    batching::{Batching, DEFAULT_BATCH_TOKENS},
+    config::{Config, Settings},
+    examples::{DEFAULT_EXAMPLE_TOKENS, Examples},
+    extraction::{BoundingBox, Candidate, ExtractedField, PrimaryRule},
+    extractor::{
+        DEFAULT_BATCH_SIZE, DEFAULT_CONCURRENCY, DEFAULT_DPI, DEFAULT_MODEL,
+        Extractor, ExtractorBuilder, Provider,
+    },
+    input::InputMode,
+    progress::ProgressEvent,
+    prompt::PromptTemplate,
+    ratelimit::RateLimits,
+    schema::{
+        Comparison, Condition, DEFAULT_MAX_DESCRIPTION_CHARS, Schema,
+        SchemaField, SchemaKind,
+    },
+    secret::ApiKey,
+    usage::Usage,
+};
+
+/// Every extracted field of one paper, keyed by field name.
+pub type Extraction = extraction::ExtractionResult;
+
+/// Errors are reported as readable messages.
+pub type Result<T> = result::Result<T, String>;
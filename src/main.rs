@@ -1,306 +1,867 @@
-mod prompt;
-mod schema;
-
-use base64::{Engine as _, engine::general_purpose};
-use clap::Parser;
-use csv::Writer;
-use prompt::build_prompt;
-use reqwest::Client;
-use schema::{SchemaField, build_json_schema, read_schema};
-use serde::Deserialize;
-use serde_json::{Value, json};
+mod reporter;
+
+use clap::parser::ValueSource;
+use clap::{
+    ArgMatches, CommandFactory as _, FromArgMatches as _, Parser, Subcommand,
+    ValueEnum as _,
+};
+use paper_extract::annotate::annotate_pdf;
+use paper_extract::output::{OutputFormat, OutputSink};
+use paper_extract::pdf::{load_pdf, page_count, parse_page_ranges};
+use paper_extract::report::{PaperReport, render_report};
+use paper_extract::review::run_review;
+use paper_extract::risk_of_bias::{BiasTool, write_assessment_csv};
+use paper_extract::screening::{Criteria, PrismaCounts, write_screening_csv};
+use paper_extract::sqlite::{RunInfo, SqliteSink};
+use paper_extract::{
+    ApiKey, Batching, Config, DEFAULT_BATCH_SIZE, DEFAULT_BATCH_TOKENS,
+    DEFAULT_CONCURRENCY, DEFAULT_DPI, DEFAULT_EXAMPLE_TOKENS,
+    DEFAULT_MAX_DESCRIPTION_CHARS, DEFAULT_MODEL, Examples, Extraction,
+    Extractor, ExtractorBuilder, InputMode, PrimaryRule, PromptTemplate,
+    Provider, RateLimits, Schema, Settings,
+};
+use reporter::{LogFormat, Reporter};
 use std::env;
-use std::fs::{self, File};
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    /// Path to the schema CSV file
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    extract: Option<ExtractArgs>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Walk through an output CSV field by field to accept, correct or
+    /// reject each extracted value.
+    Review(ReviewArgs),
+    /// Screen papers against inclusion and exclusion criteria, writing a
+    /// decision per paper and PRISMA-style counts.
+    Screen(Box<ScreenArgs>),
+    /// Answer a risk-of-bias tool's questions for each paper and derive the
+    /// domain and overall judgements from the answers.
+    RiskOfBias(Box<BiasArgs>),
+}
+
+#[derive(clap::Args, Debug)]
+struct ReviewArgs {
+    /// Output CSV written by an extraction run.
+    csv: String,
+
+    /// The paper, used to show the text under each value (defaults to the
+    /// CSV filename with .pdf extension, if it exists).
+    #[arg(long)]
+    pdf: Option<String>,
+
+    /// Name recorded with every decision (defaults to `$USER`).
+    #[arg(long)]
+    reviewer: Option<String>,
+
+    /// Where to write decisions (defaults to `<name>.reviewed.csv` next to
+    /// the CSV). Existing decisions there are resumed.
+    #[arg(long, value_name = "REVIEWED.CSV")]
+    out: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct ScreenArgs {
+    /// CSV of eligibility criteria with `name`, `description` and `type`
+    /// (`include` or `exclude`) columns.
+    criteria: String,
+
+    /// The PDF to screen, or a folder of PDFs.
+    pdf: String,
+
+    /// Where to write the screening decisions (defaults to the PDF or folder
+    /// name with `.screening.csv`).
+    #[arg(long, value_name = "SCREENING.CSV")]
+    output: Option<String>,
+
+    /// Also write the PRISMA-style counts as a CSV.
+    #[arg(long, value_name = "PRISMA.CSV")]
+    prisma: Option<String>,
+
+    #[command(flatten)]
+    request: RequestArgs,
+}
+
+#[derive(clap::Args, Debug)]
+struct BiasArgs {
+    /// The risk-of-bias tool to apply.
+    #[arg(value_enum)]
+    tool: BiasTool,
+
+    /// The PDF to assess, or a folder of PDFs.
+    pdf: String,
+
+    /// Where to write the judgements (defaults to the PDF or folder name
+    /// with the tool's name and `.csv`).
+    #[arg(long, value_name = "JUDGEMENTS.CSV")]
+    output: Option<String>,
+
+    #[command(flatten)]
+    request: RequestArgs,
+}
+
+// Flattening `RequestArgs` leaves clap's group for these arguments empty,
+// so the positionals that mark an extraction are named here.
+#[derive(clap::Args, Debug)]
+#[group(args = ["schema", "pdf"])]
+struct ExtractArgs {
+    /// Path to the schema CSV file.
     schema: String,
 
-    /// Path to the PDF file to extract data from
+    /// Path to the PDF file to extract data from, or a folder of PDFs to
+    /// process one after another.
     pdf: String,
 
-    /// Path to the output CSV file (defaults to PDF filename with .csv extension)
+    /// Longest field description accepted in the schema, in characters.
+    /// Longer instructions belong in the schema's `guidance` column.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_DESCRIPTION_CHARS)]
+    max_description_chars: usize,
+
+    /// Path to the output file (defaults to PDF filename with the extension
+    /// of `--format`). Not allowed with a folder, where each file is written
+    /// next to its PDF, except for JSONL and XLSX, which collect all papers
+    /// in one file (defaults to the folder name with the format's extension).
     output: Option<String>,
 
-    /// Number of fields to process in each batch
-    #[arg(long, default_value_t = 20)]
-    batch: usize,
+    /// Format of the output file.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Csv)]
+    format: OutputFormat,
+
+    /// With a folder, overwrite output files that already exist next to the
+    /// PDFs, such as reviewed CSVs from an earlier run.
+    #[arg(long)]
+    force: bool,
+
+    #[command(flatten)]
+    request: RequestArgs,
+
+    /// Folder of output CSVs from earlier papers, ideally reviewed, whose
+    /// values are shown to the model as examples under each field. PDFs next
+    /// to the CSVs supply the text each value was found in.
+    #[arg(long, value_name = "DIR")]
+    examples: Option<PathBuf>,
+
+    /// Estimated tokens of examples allowed in each request.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_EXAMPLE_TOKENS)]
+    example_tokens: usize,
+
+    /// Which candidate becomes a field's value when the paper reports
+    /// several: the model's answer, the first or last in the paper, or the
+    /// value reported most often.
+    #[arg(long, value_enum, default_value_t = PrimaryRule::Model)]
+    primary: PrimaryRule,
+
+    /// Also write a copy of the PDF with every located value highlighted.
+    #[arg(long, value_name = "OUT.PDF")]
+    annotate: Option<String>,
+
+    /// Also store results in a `SQLite` database, updated as each batch
+    /// completes. Re-running a paper updates its rows in place.
+    #[arg(long, value_name = "RESULTS.DB")]
+    sqlite: Option<String>,
+
+    /// Also write a self-contained HTML report for reviewing the results,
+    /// with fill rates across papers when a folder is processed.
+    #[arg(long, value_name = "REPORT.HTML")]
+    report: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct ExtractedField {
-    value: Option<serde_json::Value>,
-    match_type: String,
-    comment: Option<String>,
-    page: i64,
-    xmin: f64,
-    ymin: f64,
-    xmax: f64,
-    ymax: f64,
+// How papers are sent to the model, shared by extraction and screening.
+// Doc comments here would replace the command's about text.
+#[derive(clap::Args, Debug)]
+struct RequestArgs {
+    /// Number of fields to process in each batch, `auto` to size batches by
+    /// estimated output tokens, or `all` to send every field in one request.
+    /// Fields sharing a `batch` key in the schema are always sent together.
+    #[arg(long, default_value_t = DEFAULT_BATCH_SIZE.to_string())]
+    batch: String,
+
+    /// Estimated output tokens per batch with `--batch auto`.
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_BATCH_TOKENS)]
+    batch_tokens: usize,
+
+    /// Maximum number of requests in flight at once. The first request for
+    /// each page window is sent on its own, so the others can reuse the
+    /// document from the provider's prompt cache.
+    #[arg(long, default_value_t = DEFAULT_CONCURRENCY)]
+    concurrency: usize,
+
+    /// Requests per minute allowed by the provider; requests wait rather than
+    /// exceed it.
+    #[arg(long, value_name = "N")]
+    requests_per_minute: Option<u32>,
+
+    /// Tokens per minute allowed by the provider, estimated from each
+    /// request's text, pages and expected answer.
+    #[arg(long, value_name = "N")]
+    tokens_per_minute: Option<u32>,
+
+    /// Model to request.
+    #[arg(long, default_value = DEFAULT_MODEL)]
+    model: String,
+
+    /// Send requests to an OpenAI-compatible server at this URL (up to but
+    /// not including `/chat/completions`) instead of `OpenRouter`.
+    #[arg(long, value_name = "URL")]
+    base_url: Option<String>,
+
+    /// Read the API key from this file instead of the provider's environment
+    /// variable. The file must only be readable by you (`chmod 600`).
+    #[arg(long, value_name = "PATH")]
+    api_key_file: Option<PathBuf>,
+
+    /// API key from the config file.
+    #[arg(skip)]
+    api_key: Option<ApiKey>,
+
+    /// Pages to send to the model, e.g. `1-12` or `1-3,5` (1-based).
+    #[arg(long)]
+    pages: Option<String>,
+
+    /// Markdown file with the instructions to send instead of the built-in
+    /// prompt. It may use `{{FIELDS_LIST}}` (required), `{{FILENAME}}`,
+    /// `{{DOMAIN_CONTEXT}}`, `{{OUTPUT_FORMAT}}`, `{{BATCH_INDEX}}` and
+    /// `{{BATCH_COUNT}}`.
+    #[arg(long, value_name = "PATH.md")]
+    prompt_template: Option<PathBuf>,
+
+    /// File with a paragraph of background on the research area, included
+    /// in the prompt as `{{DOMAIN_CONTEXT}}`.
+    #[arg(long, value_name = "PATH")]
+    domain_context: Option<PathBuf>,
+
+    /// Drop the reference list and any supplements that follow it.
+    #[arg(long)]
+    exclude_references: bool,
+
+    /// Split the document into windows of this many pages and merge the
+    /// per-window results, preferring found values over inferred ones.
+    #[arg(long)]
+    chunk_pages: Option<usize>,
+
+    /// How to send the document: as a PDF file, or as locally extracted text
+    /// for providers and local models without PDF support.
+    #[arg(long, value_enum, default_value_t = InputMode::Pdf)]
+    input_mode: InputMode,
+
+    /// Resolution used to rasterize pages in `--input-mode images`.
+    #[arg(long, default_value_t = DEFAULT_DPI)]
+    dpi: u32,
+
+    /// How to write progress: text with a progress bar, or one JSON object
+    /// per line.
+    #[arg(long, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// Use the settings of `[profile.NAME]` in `paper-extract.toml`. Flags
+    /// given on the command line override the config file.
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
 }
 
-use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::task::JoinHandle;
-type ExtractionResult = HashMap<String, ExtractedField>;
+impl ExtractArgs {
+    /// Determine output path - use provided path or default to PDF name with
+    /// the format's extension. JSONL and XLSX collect every paper in one
+    /// file, named after the PDF or folder.
+    fn output_path(&self, pdf_path: &Path) -> String {
+        let named_after = if self.format.is_corpus() {
+            Path::new(&self.pdf)
+        } else {
+            pdf_path
+        };
+        self.output.clone().unwrap_or_else(|| {
+            named_after
+                .with_extension(self.format.extension())
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    match (cli.command, cli.extract) {
+        (Some(Command::Review(args)), _) => review(&args),
+        (Some(Command::Screen(mut args)), _) => {
+            if let Some(screen_matches) = matches.subcommand_matches("screen") {
+                apply_request_config(&mut args.request, screen_matches);
+            }
+            screen(*args).await;
+        }
+        (Some(Command::RiskOfBias(mut args)), _) => {
+            if let Some(bias_matches) =
+                matches.subcommand_matches("risk-of-bias")
+            {
+                apply_request_config(&mut args.request, bias_matches);
+            }
+            assess_risk_of_bias(*args).await;
+        }
+        (None, Some(mut args)) => {
+            apply_config(&mut args, &matches);
+            extract(args).await;
+        }
+        (None, None) => panic!("Missing schema and PDF arguments"),
+    }
+}
 
-    // Determine output path - use provided path or default to PDF name with .csv extension
-    let output_path = args.output.unwrap_or_else(|| {
-        let mut path = PathBuf::from(&args.pdf);
-        path.set_extension("csv");
-        path.to_string_lossy().into_owned()
-    });
+fn review(args: &ReviewArgs) {
+    let reviewer = args
+        .reviewer
+        .clone()
+        .or_else(|| env::var("USER").ok())
+        .expect("Pass --reviewer to record who made each decision");
+    run_review(
+        Path::new(&args.csv),
+        args.pdf.as_deref().map(Path::new),
+        &reviewer,
+        args.out.as_deref().map(Path::new),
+    )
+    .unwrap_or_else(|e| panic!("{e}"));
+}
 
-    println!("Processing {} -> {}", args.pdf, output_path);
+async fn extract(args: ExtractArgs) {
+    let pdf_paths = collect_pdfs(&args.pdf);
+    if Path::new(&args.pdf).is_dir() {
+        check_folder_args(&args, &pdf_paths);
+    }
 
-    let schema = read_schema(&args.schema);
+    let schema = Schema::from_path_with_max_description(
+        &args.schema,
+        args.max_description_chars,
+    )
+    .unwrap_or_else(|e| panic!("{e}"));
+    let extractor = build_extractor(&args, schema.clone());
+    let sqlite = open_sqlite(&args, &extractor);
+
+    let mut reporter = Reporter::new(args.request.log_format);
+    let mut reports = Vec::new();
+    let prompt_hash = extractor.prompt_hash();
+    reporter.message(&format!("Prompt hash: {prompt_hash}"));
+    let mut outputs = OutputSink::new(
+        args.format,
+        args.output_path(Path::new(&args.pdf)),
+        prompt_hash,
+    );
+
+    for pdf_path in &pdf_paths {
+        let output_path = args.output_path(pdf_path);
+
+        reporter.message(&format!(
+            "Processing {} -> {}",
+            pdf_path.display(),
+            output_path
+        ));
+
+        let pdf_data = fs::read(pdf_path).expect("Failed to read PDF file");
+        let document = load_pdf(&pdf_data).unwrap_or_else(|e| panic!("{e}"));
+        let name = pdf_path.file_name().map_or_else(
+            || pdf_path.to_string_lossy(),
+            |name| name.to_string_lossy(),
+        );
 
-    // Split schema into batches
-    let batches: Vec<Vec<SchemaField>> = schema
-        .chunks(args.batch)
-        .map(<[SchemaField]>::to_vec)
-        .collect();
+        let stored_paper = sqlite.as_ref().map(|sink| {
+            let paper_id = sink
+                .begin_paper(pdf_path, &name, page_count(&document))
+                .unwrap_or_else(|e| panic!("{e}"));
+            (sink, paper_id)
+        });
+
+        let all_results = extract_paper(
+            &extractor,
+            &pdf_data,
+            &name,
+            stored_paper,
+            &mut reporter,
+        )
+        .await;
+
+        if let Some((sink, paper_id)) = stored_paper {
+            sink.upsert_fields(paper_id, &all_results)
+                .and_then(|()| sink.finish_paper(paper_id))
+                .unwrap_or_else(|e| panic!("{e}"));
+        }
+
+        outputs.write_paper(&output_path, &name, &all_results, schema.fields());
+        if !args.format.is_corpus() {
+            reporter
+                .message(&format!("Done! Results written to {output_path}"));
+        }
 
-    let pdf_base64 = pdf_to_base64(&args.pdf);
+        if let Some(annotate_path) = args.annotate.as_deref() {
+            let annotated =
+                annotate_pdf(&document, &all_results, schema.fields())
+                    .unwrap_or_else(|e| panic!("{e}"));
+            fs::write(annotate_path, annotated)
+                .expect("Failed to write annotated PDF");
+            reporter
+                .message(&format!("Annotated PDF written to {annotate_path}"));
+        }
 
-    let api_key = env::var("OPENROUTER_API_KEY")
-        .expect("OPENROUTER_API_KEY environment variable not set");
+        if args.report.is_some() {
+            let report =
+                PaperReport::new(&name, &pdf_data, &document, all_results);
+            if let Some(warning) = report.warning() {
+                reporter.message(&format!("Warning: {warning}"));
+            }
+            reports.push(report);
+        }
+    }
 
-    // Process each batch in parallel
-    let pdf_base64_arc = Arc::new(pdf_base64);
-    let api_key_arc = Arc::new(api_key);
+    if let Some(sink) = sqlite.as_ref() {
+        sink.finish_run().unwrap_or_else(|e| panic!("{e}"));
+        reporter.message(&format!(
+            "Results stored in {}",
+            args.sqlite.as_deref().unwrap_or_default()
+        ));
+    }
 
-    println!("Processing {} batches in parallel...", batches.len());
+    if let Some(path) = outputs.finish(schema.fields()) {
+        reporter.message(&format!("Done! Results written to {path}"));
+    }
 
-    let mut tasks: Vec<JoinHandle<(usize, ExtractionResult)>> = Vec::new();
+    if let Some(report_path) = args.report.as_deref() {
+        let html = render_report(schema.fields(), &reports)
+            .unwrap_or_else(|e| panic!("{e}"));
+        fs::write(report_path, html).expect("Failed to write report");
+        reporter.message(&format!("Report written to {report_path}"));
+    }
 
-    for (batch_idx, batch_fields) in batches.into_iter().enumerate() {
-        let pdf_base64_clone = Arc::clone(&pdf_base64_arc);
-        let api_key_clone = Arc::clone(&api_key_arc);
-        let batch_fields_owned = batch_fields.clone();
+    reporter.finish();
+}
 
-        let task = tokio::spawn(async move {
-            println!(
-                "Starting batch {} ({} fields)",
-                batch_idx.saturating_add(1),
-                batch_fields_owned.len()
-            );
+/// Extracts one paper, rendering its progress events as they arrive and
+/// storing each completed batch in the `SQLite` database, if any.
+async fn extract_paper(
+    extractor: &Extractor,
+    pdf_data: &[u8],
+    name: &str,
+    stored_paper: Option<(&SqliteSink, i64)>,
+    reporter: &mut Reporter,
+) -> Extraction {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let extraction = extractor.extract_with_events(pdf_data, name, sender);
+    let render = async {
+        while let Some(event) = receiver.recv().await {
+            if let Some((sink, paper_id)) = stored_paper
+                && let Some(results) = event.batch_results()
+            {
+                sink.upsert_fields(paper_id, results)
+                    .unwrap_or_else(|e| panic!("{e}"));
+            }
+            reporter.event(name, event);
+        }
+    };
+
+    let (all_results, ()) = tokio::join!(extraction, render);
+    all_results.unwrap_or_else(|e| panic!("{e}"))
+}
+
+/// Screens every paper against the criteria, writing one decision per paper
+/// and the PRISMA-style counts of the run.
+async fn screen(args: ScreenArgs) {
+    let pdf_paths = collect_pdfs(&args.pdf);
+    let criteria =
+        Criteria::from_path(&args.criteria).unwrap_or_else(|e| panic!("{e}"));
+    let extractor = request_builder(&args.request, criteria.schema())
+        .build()
+        .unwrap_or_else(|e| panic!("{e}"));
+    let output_path = args.output.clone().unwrap_or_else(|| {
+        Path::new(&args.pdf)
+            .with_extension("screening.csv")
+            .to_string_lossy()
+            .into_owned()
+    });
 
-            let response = call_openrouter(
-                (*pdf_base64_clone).clone(),
-                &batch_fields_owned,
-                &api_key_clone,
+    let mut reporter = Reporter::new(args.request.log_format);
+    let mut screenings = Vec::new();
+    let mut counts = PrismaCounts::new(&criteria);
+    for pdf_path in &pdf_paths {
+        reporter.message(&format!("Screening {}", pdf_path.display()));
+        let pdf_data = fs::read(pdf_path).expect("Failed to read PDF file");
+        let name = pdf_path.file_name().map_or_else(
+            || pdf_path.to_string_lossy(),
+            |name| name.to_string_lossy(),
+        );
+
+        let verdicts =
+            extract_paper(&extractor, &pdf_data, &name, None, &mut reporter)
+                .await;
+        let screening = criteria.screen(&name, &verdicts);
+        let decision = if screening.reasons.is_empty() {
+            format!("Decision: {}", screening.decision.name())
+        } else {
+            format!(
+                "Decision: {} ({})",
+                screening.decision.name(),
+                screening.reasons.join(", ")
             )
-            .await;
-
-            // Extract results from response
-            let content = response
-                .get("choices")
-                .and_then(|choices| choices.get(0))
-                .and_then(|choice| choice.get("message"))
-                .and_then(|message| message.get("content"))
-                .unwrap_or_else(|| {
-                    eprintln!("Unexpected response structure");
-                    eprintln!(
-                        "Full response: {}",
-                        serde_json::to_string_pretty(&response).unwrap_or_else(
-                            |_| "<failed to serialize>".to_owned()
-                        )
-                    );
-                    panic!("Failed to extract content from API response");
-                });
-
-            let content_str = content.as_str().unwrap_or_else(|| {
-                eprintln!("Content is not a string: {content}");
-                panic!("Expected string content in response");
-            });
-
-            let batch_results: ExtractionResult =
-                serde_json::from_str(content_str).unwrap_or_else(|e| {
-                    eprintln!("Failed to parse extracted data: {e}");
-                    eprintln!("Raw content: {content_str}");
-                    panic!(
-                        "Failed to parse extracted data into ExtractionResult"
-                    );
-                });
-
-            println!(
-                "Completed batch {} ({} fields extracted)",
-                batch_idx.saturating_add(1),
-                batch_results.len()
-            );
-
-            (batch_idx, batch_results)
-        });
+        };
+        reporter.message(&decision);
+        counts.add(&screening);
+        screenings.push(screening);
+    }
 
-        tasks.push(task);
+    write_screening_csv(&output_path, &criteria, &screenings)
+        .unwrap_or_else(|e| panic!("{e}"));
+    reporter.message(&format!("Decisions written to {output_path}"));
+    for line in counts.to_string().lines() {
+        reporter.message(line);
+    }
+    if let Some(prisma_path) = args.prisma.as_deref() {
+        counts
+            .write_csv(prisma_path)
+            .unwrap_or_else(|e| panic!("{e}"));
+        reporter.message(&format!("PRISMA counts written to {prisma_path}"));
     }
 
-    // Wait for all tasks to complete and merge results
-    let mut all_results = HashMap::new();
-    for task in tasks {
-        let (_batch_idx, batch_results) = task.await.expect("Task failed");
-        all_results.extend(batch_results);
+    reporter.finish();
+}
+
+/// Answers the tool's questions for every paper and writes the judgements
+/// derived from them, one row per paper.
+async fn assess_risk_of_bias(args: BiasArgs) {
+    let pdf_paths = collect_pdfs(&args.pdf);
+    let mut builder = request_builder(&args.request, args.tool.schema());
+    if args.request.domain_context.is_none() {
+        builder = builder.domain_context(args.tool.context());
     }
+    let extractor = builder.build().unwrap_or_else(|e| panic!("{e}"));
+    let output_path = args.output.clone().unwrap_or_else(|| {
+        Path::new(&args.pdf)
+            .with_extension(format!("{}.csv", args.tool.name()))
+            .to_string_lossy()
+            .into_owned()
+    });
 
-    println!("All batches completed. Writing results to CSV...");
+    let mut reporter = Reporter::new(args.request.log_format);
+    let mut assessments = Vec::new();
+    for pdf_path in &pdf_paths {
+        reporter.message(&format!("Assessing {}", pdf_path.display()));
+        let pdf_data = fs::read(pdf_path).expect("Failed to read PDF file");
+        let name = pdf_path.file_name().map_or_else(
+            || pdf_path.to_string_lossy(),
+            |name| name.to_string_lossy(),
+        );
 
-    write_csv(&output_path, &all_results, &schema);
-    println!("Done! Results written to {output_path}");
-}
+        let answers =
+            extract_paper(&extractor, &pdf_data, &name, None, &mut reporter)
+                .await;
+        let assessment = args.tool.assess(&name, &answers);
+        let domains: Vec<String> = assessment
+            .domains
+            .iter()
+            .map(|domain| format!("{}: {}", domain.domain, domain.judgement))
+            .collect();
+        reporter.message(&format!(
+            "Overall: {} ({})",
+            assessment.overall,
+            domains.join(", ")
+        ));
+        assessments.push(assessment);
+    }
 
-fn pdf_to_base64(path: &str) -> String {
-    let pdf_data = fs::read(path).expect("Failed to read PDF file");
-    let base64_data = general_purpose::STANDARD.encode(pdf_data);
-    format!("data:application/pdf;base64,{base64_data}")
+    write_assessment_csv(&output_path, args.tool, &assessments)
+        .unwrap_or_else(|e| panic!("{e}"));
+    reporter.message(&format!("Judgements written to {output_path}"));
+    reporter.finish();
 }
 
-async fn call_openrouter(
-    pdf_base64: String,
-    fields: &[SchemaField],
-    api_key: &str,
-) -> Value {
-    let client = Client::new();
+/// Fills in every setting not given on the command line from
+/// `paper-extract.toml`, if there is one: the `--profile` first, then the
+/// file's top-level settings.
+fn apply_config(args: &mut ExtractArgs, matches: &ArgMatches) {
+    let Some(settings) = apply_request_config(&mut args.request, matches)
+    else {
+        return;
+    };
+    let from_config =
+        |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+    if from_config("examples") && settings.examples.is_some() {
+        args.examples = settings.examples;
+    }
+    if from_config("example_tokens")
+        && let Some(tokens) = settings.example_tokens
+    {
+        args.example_tokens = tokens;
+    }
+    if from_config("format")
+        && let Some(format) = settings.format
+    {
+        args.format = format;
+    }
+    if from_config("primary")
+        && let Some(rule) = settings.primary
+    {
+        args.primary = rule;
+    }
+    if from_config("max_description_chars")
+        && let Some(chars) = settings.max_description_chars
+    {
+        args.max_description_chars = chars;
+    }
+}
 
-    let json_schema = build_json_schema(fields);
-    let prompt = build_prompt(fields);
+/// Fills in the settings shared by extraction and screening, returning the
+/// config file's settings for the command's own, if there is a config file.
+fn apply_request_config(
+    args: &mut RequestArgs,
+    matches: &ArgMatches,
+) -> Option<Settings> {
+    let discovered = Config::discover().unwrap_or_else(|e| panic!("{e}"));
+    let Some(config) = discovered else {
+        assert!(
+            args.profile.is_none(),
+            "--profile needs a paper-extract.toml in the working directory or $XDG_CONFIG_HOME"
+        );
+        return None;
+    };
+    let settings = config
+        .settings(args.profile.as_deref())
+        .unwrap_or_else(|e| panic!("{e}"));
+    let from_config =
+        |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+
+    if from_config("base_url") && settings.base_url.is_some() {
+        args.base_url.clone_from(&settings.base_url);
+    }
+    if from_config("api_key_file") && settings.api_key_file.is_some() {
+        args.api_key_file.clone_from(&settings.api_key_file);
+    }
+    args.api_key.clone_from(&settings.api_key);
+    if from_config("prompt_template") && settings.prompt_template.is_some() {
+        args.prompt_template.clone_from(&settings.prompt_template);
+    }
+    if from_config("domain_context") && settings.domain_context.is_some() {
+        args.domain_context.clone_from(&settings.domain_context);
+    }
+    if from_config("model")
+        && let Some(model) = settings.model.as_ref()
+    {
+        args.model.clone_from(model);
+    }
+    if from_config("batch")
+        && let Some(batch) = settings.batch.as_ref()
+    {
+        args.batch = batch.to_string();
+    }
+    if from_config("batch_tokens")
+        && let Some(tokens) = settings.batch_tokens
+    {
+        args.batch_tokens = tokens;
+    }
+    if from_config("concurrency")
+        && let Some(concurrency) = settings.concurrency
+    {
+        args.concurrency = concurrency;
+    }
+    if from_config("requests_per_minute")
+        && settings.requests_per_minute.is_some()
+    {
+        args.requests_per_minute = settings.requests_per_minute;
+    }
+    if from_config("tokens_per_minute") && settings.tokens_per_minute.is_some()
+    {
+        args.tokens_per_minute = settings.tokens_per_minute;
+    }
+    if from_config("input_mode")
+        && let Some(input_mode) = settings.input_mode
+    {
+        args.input_mode = input_mode;
+    }
+    if from_config("dpi")
+        && let Some(dpi) = settings.dpi
+    {
+        args.dpi = dpi;
+    }
+    Some(settings)
+}
 
-    let request_body = json!({
-        "model": "openai/gpt-5-mini",
-        "messages": [
-            {
-                "role": "user",
-                "content": [
-                    {
-                        "type": "text",
-                        "text": prompt
-                    },
-                    {
-                        "type": "file",
-                        "file": {
-                            "filename": "document.pdf",
-                            "file_data": pdf_base64,
-                        }
-                    }
-                ]
-            }
-        ],
-        "response_format": {
-            "type": "json_schema",
-            "json_schema": {
-                "name": "extraction",
-                "strict": true,
-                "schema": json_schema
-            }
-        }
-    });
+/// Configures the extractor from the command line.
+fn build_extractor(args: &ExtractArgs, schema: Schema) -> Extractor {
+    let mut builder =
+        request_builder(&args.request, schema).primary_rule(args.primary);
+    if let Some(dir) = args.examples.as_deref() {
+        builder = builder.examples(
+            Examples::from_dir(dir, args.example_tokens)
+                .unwrap_or_else(|e| panic!("{e}")),
+        );
+    }
+    builder.build().unwrap_or_else(|e| panic!("{e}"))
+}
 
-    let response = client
-        .post("https://openrouter.ai/api/v1/chat/completions")
-        .header("Authorization", format!("Bearer {api_key}"))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-        .expect("Failed to send request to OpenRouter");
-
-    let status = response.status();
-    let response_text = response.text().await.expect("Failed to read response");
-
-    // Parse the response JSON
-    let response_json: Value = serde_json::from_str(&response_text)
-        .unwrap_or_else(|e| {
-            eprintln!("Failed to parse JSON response: {e}");
-            eprintln!("Raw response: {response_text}");
-            panic!("Invalid JSON response from API");
+/// Configures how requests are sent, for extraction and screening alike.
+/// `OpenRouter` needs an API key; an OpenAI-compatible server uses one if
+/// there is one.
+fn request_builder(args: &RequestArgs, schema: Schema) -> ExtractorBuilder {
+    let pages = args
+        .pages
+        .as_deref()
+        .map(parse_page_ranges)
+        .transpose()
+        .unwrap_or_else(|e| panic!("{e}"));
+    let batching = if args.batch == "auto" {
+        Batching::OutputTokens(args.batch_tokens)
+    } else if args.batch == "all" {
+        Batching::All
+    } else {
+        Batching::Fields(args.batch.parse().unwrap_or_else(|_| {
+            panic!(
+                "Invalid batch size '{}': use a number, auto or all",
+                args.batch
+            )
+        }))
+    };
+    let provider = args
+        .base_url
+        .as_deref()
+        .map_or(Provider::OpenRouter, |url| {
+            Provider::OpenAiCompatible(url.to_owned())
         });
-
-    // Check if the response indicates an error
-    if !status.is_success() || response_json.get("error").is_some() {
-        eprintln!("API request failed with status: {status}");
-        eprintln!(
-            "Full response: {}",
-            serde_json::to_string_pretty(&response_json)
-                .unwrap_or(response_text)
+    let api_key = find_api_key(args, &provider);
+    assert!(
+        api_key.is_some() || provider != Provider::OpenRouter,
+        "No API key for OpenRouter: set OPENROUTER_API_KEY, pass --api-key-file or add api-key to paper-extract.toml"
+    );
+
+    let mut builder = Extractor::builder(schema)
+        .provider(provider)
+        .model(&args.model)
+        .batching(batching)
+        .concurrency(args.concurrency)
+        .rate_limits(RateLimits {
+            requests_per_minute: args.requests_per_minute,
+            tokens_per_minute: args.tokens_per_minute,
+        })
+        .exclude_references(args.exclude_references)
+        .input_mode(args.input_mode)
+        .dpi(args.dpi);
+    if let Some(key) = api_key {
+        builder = builder.api_key(key);
+    }
+    if let Some(selected) = pages {
+        builder = builder.pages(selected);
+    }
+    if let Some(window) = args.chunk_pages {
+        builder = builder.chunk_pages(window);
+    }
+    if let Some(path) = args.prompt_template.as_deref() {
+        builder = builder.prompt_template(
+            PromptTemplate::from_path(path).unwrap_or_else(|e| panic!("{e}")),
         );
+    }
+    if let Some(path) = args.domain_context.as_deref() {
+        builder = builder.domain_context(
+            fs::read_to_string(path).unwrap_or_else(|e| {
+                panic!("Failed to read domain context {}: {e}", path.display())
+            }),
+        );
+    }
 
-        if let Some(error) = response_json.get("error") {
-            if let Some(message) = error.get("message") {
-                panic!(
-                    "API error: {}",
-                    message.as_str().unwrap_or("Unknown error")
-                );
-            }
-        }
-        panic!("API request failed with status: {status}");
+    builder
+}
+
+/// Finds the API key: `--api-key-file`, then the config file, then the
+/// provider's environment variable.
+fn find_api_key(args: &RequestArgs, provider: &Provider) -> Option<ApiKey> {
+    if let Some(path) = args.api_key_file.as_deref() {
+        return Some(ApiKey::from_file(path).unwrap_or_else(|e| panic!("{e}")));
     }
+    args.api_key.clone().or_else(|| {
+        env::var(provider.api_key_variable())
+            .ok()
+            .filter(|key| !key.trim().is_empty())
+            .map(ApiKey::from)
+    })
+}
 
-    response_json
+fn check_folder_args(args: &ExtractArgs, pdf_paths: &[PathBuf]) {
+    assert!(
+        args.annotate.is_none(),
+        "--annotate can only be given for a single PDF"
+    );
+    assert!(
+        args.output.is_none() || args.format.is_corpus(),
+        "An output path can only be given for a single PDF or with --format jsonl or xlsx"
+    );
+    if args.force || args.format.is_corpus() {
+        return;
+    }
+    let existing: Vec<String> = pdf_paths
+        .iter()
+        .map(|pdf_path| args.output_path(pdf_path))
+        .filter(|output_path| Path::new(output_path).exists())
+        .collect();
+    assert!(
+        existing.is_empty(),
+        "Output files already exist next to the PDFs: {}. Pass --force to overwrite them",
+        existing.join(", ")
+    );
 }
 
-fn write_csv(
-    output_path: &str,
-    extracted_data: &ExtractionResult,
-    fields: &[SchemaField],
-) {
-    let file = File::create(output_path).expect("Failed to create output file");
-    let mut writer = Writer::from_writer(file);
-
-    let headers = vec![
-        "field_name",
-        "value",
-        "match_type",
-        "comment",
-        "page",
-        "xmin",
-        "ymin",
-        "xmax",
-        "ymax",
-    ];
-    writer
-        .write_record(&headers)
-        .expect("Failed to write headers");
-
-    for field in fields {
-        let field_data =
-            extracted_data.get(&field.field_name).unwrap_or_else(|| {
-                panic!(
-                    "Field {} not found in extraction result",
-                    field.field_name
-                )
-            });
-
-        let value = match field_data.value.clone() {
-            Some(Value::String(string_val)) => string_val,
-            Some(Value::Number(number_val)) => number_val.to_string(),
-            Some(Value::Bool(bool_val)) => bool_val.to_string(),
-            Some(Value::Null) | None => String::new(),
-            Some(value_obj) => {
-                serde_json::to_string(&value_obj).unwrap_or_default()
-            }
+/// Opens the `--sqlite` database and records the start of this run.
+fn open_sqlite(
+    args: &ExtractArgs,
+    extractor: &Extractor,
+) -> Option<SqliteSink> {
+    args.sqlite.as_deref().map(|path| {
+        let prompt_hash = extractor.prompt_hash();
+        let input_mode = args
+            .request
+            .input_mode
+            .to_possible_value()
+            .map(|value| value.get_name().to_owned())
+            .unwrap_or_default();
+        let run = RunInfo {
+            model: &args.request.model,
+            schema_path: &args.schema,
+            input: &args.pdf,
+            input_mode: &input_mode,
+            prompt_hash: &prompt_hash,
         };
+        SqliteSink::open(Path::new(path), extractor.schema().fields(), &run)
+            .unwrap_or_else(|e| panic!("{e}"))
+    })
+}
 
-        let row = vec![
-            field.field_name.clone(),
-            value,
-            field_data.match_type.clone(),
-            field_data.comment.clone().unwrap_or_default(),
-            field_data.page.to_string(),
-            field_data.xmin.to_string(),
-            field_data.ymin.to_string(),
-            field_data.xmax.to_string(),
-            field_data.ymax.to_string(),
-        ];
-
-        writer.write_record(&row).expect("Failed to write data row");
+/// Returns the PDF itself, or every PDF directly inside a folder in name
+/// order.
+fn collect_pdfs(path: &str) -> Vec<PathBuf> {
+    if !Path::new(path).is_dir() {
+        return vec![PathBuf::from(path)];
     }
 
-    writer.flush().expect("Failed to flush CSV writer");
+    let mut pdfs: Vec<PathBuf> = fs::read_dir(path)
+        .expect("Failed to read PDF folder")
+        .map(|entry| entry.expect("Failed to read PDF folder").path())
+        .filter(|entry| {
+            entry.is_file()
+                && entry.extension().is_some_and(|extension| {
+                    extension.eq_ignore_ascii_case("pdf")
+                })
+        })
+        .collect();
+    pdfs.sort();
+
+    assert!(!pdfs.is_empty(), "No PDF files found in {path}");
+    pdfs
 }
@@ -0,0 +1,417 @@
+use crate::extraction::{BoundingBox, Candidate, ExtractionResult, value_text};
+use crate::schema::SchemaField;
+#[cfg(feature = "xlsx")]
+use crate::xlsx::{PaperResults, write_xlsx};
+use clap::ValueEnum;
+use csv::{Reader, Writer};
+use serde::ser::{SerializeMap as _, Serializer};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+/// File format of the extraction results.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "`Format` alone would clash with the other formats the binary names"
+)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum OutputFormat {
+    /// One row per field; non-string values are written as JSON text.
+    Csv,
+    /// One JSON document per paper, with typed values.
+    Json,
+    /// One JSON record per line, a single file for a whole folder of papers.
+    Jsonl,
+    /// An Excel workbook with a summary sheet and one sheet per paper, a
+    /// single file for a whole folder of papers.
+    #[cfg(feature = "xlsx")]
+    Xlsx,
+}
+
+impl OutputFormat {
+    #[must_use]
+    #[inline]
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+            Self::Jsonl => "jsonl",
+            #[cfg(feature = "xlsx")]
+            Self::Xlsx => "xlsx",
+        }
+    }
+
+    /// Whether all papers of a run go into one file.
+    #[must_use]
+    #[inline]
+    pub const fn is_corpus(self) -> bool {
+        match self {
+            Self::Csv | Self::Json => false,
+            Self::Jsonl => true,
+            #[cfg(feature = "xlsx")]
+            Self::Xlsx => true,
+        }
+    }
+}
+
+/// Columns of the long, one row per field, output layout.
+pub const CSV_HEADERS: [&str; 10] = [
+    "field_name",
+    "value",
+    "match_type",
+    "comment",
+    "page",
+    "xmin",
+    "ymin",
+    "xmax",
+    "ymax",
+    "candidates",
+];
+
+/// The results for one paper, with fields in schema order.
+#[derive(Serialize)]
+pub struct PaperRecord<'a> {
+    paper: &'a str,
+    prompt_hash: &'a str,
+    fields: SchemaOrdered<'a>,
+}
+
+struct SchemaOrdered<'a> {
+    results: &'a ExtractionResult,
+    fields: &'a [SchemaField],
+}
+
+impl Serialize for SchemaOrdered<'_> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+        for field in self.fields {
+            if let Some(extracted) = self.results.get(&field.field_name) {
+                map.serialize_entry(&field.field_name, extracted)?;
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'a> PaperRecord<'a> {
+    #[must_use]
+    #[inline]
+    pub const fn new(
+        paper: &'a str,
+        prompt_hash: &'a str,
+        results: &'a ExtractionResult,
+        fields: &'a [SchemaField],
+    ) -> Self {
+        Self {
+            paper,
+            prompt_hash,
+            fields: SchemaOrdered { results, fields },
+        }
+    }
+}
+
+/// Writes each paper's results as it finishes. CSV and JSON get one file
+/// per paper; JSONL and XLSX collect the whole run in one file.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "the binary holds it next to `SqliteSink`"
+)]
+pub struct OutputSink {
+    format: OutputFormat,
+    corpus_path: String,
+    prompt_hash: String,
+    jsonl: Option<BufWriter<File>>,
+    #[cfg(feature = "xlsx")]
+    workbook: Vec<PaperResults>,
+}
+
+impl OutputSink {
+    /// `corpus_path` is where JSONL and XLSX output goes; the JSONL file is
+    /// created straight away so records can be appended as papers finish.
+    /// `prompt_hash` is recorded with every paper in JSON, JSONL and XLSX.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the JSONL file cannot be created.
+    #[must_use]
+    #[inline]
+    pub fn new(
+        format: OutputFormat,
+        corpus_path: String,
+        prompt_hash: String,
+    ) -> Self {
+        let jsonl = (format == OutputFormat::Jsonl).then(|| {
+            BufWriter::new(
+                File::create(&corpus_path)
+                    .expect("Failed to create output file"),
+            )
+        });
+        Self {
+            format,
+            corpus_path,
+            prompt_hash,
+            jsonl,
+            #[cfg(feature = "xlsx")]
+            workbook: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn write_paper(
+        &mut self,
+        output_path: &str,
+        name: &str,
+        results: &ExtractionResult,
+        fields: &[SchemaField],
+    ) {
+        let record = PaperRecord::new(name, &self.prompt_hash, results, fields);
+        match self.format {
+            OutputFormat::Csv => write_csv(output_path, results, fields),
+            OutputFormat::Json => write_json(output_path, &record),
+            OutputFormat::Jsonl => {
+                if let Some(writer) = self.jsonl.as_mut() {
+                    write_jsonl_record(writer, &record);
+                }
+            }
+            #[cfg(feature = "xlsx")]
+            OutputFormat::Xlsx => {
+                self.workbook.push(PaperResults {
+                    name: name.to_owned(),
+                    results: results.clone(),
+                });
+            }
+        }
+    }
+
+    /// Completes the run-wide file, returning its path for JSONL and XLSX.
+    ///
+    /// # Panics
+    ///
+    /// Panics when the JSONL file cannot be flushed or the workbook cannot be
+    /// written.
+    #[cfg_attr(
+        not(feature = "xlsx"),
+        expect(
+            unused_variables,
+            reason = "only the workbook needs the fields"
+        )
+    )]
+    #[must_use]
+    #[inline]
+    pub fn finish(self, fields: &[SchemaField]) -> Option<String> {
+        match self.format {
+            OutputFormat::Csv | OutputFormat::Json => return None,
+            OutputFormat::Jsonl => {
+                if let Some(mut writer) = self.jsonl {
+                    writer.flush().expect("Failed to flush JSONL writer");
+                }
+            }
+            #[cfg(feature = "xlsx")]
+            OutputFormat::Xlsx => {
+                write_xlsx(
+                    &self.corpus_path,
+                    &self.prompt_hash,
+                    fields,
+                    &self.workbook,
+                )
+                .unwrap_or_else(|e| panic!("{e}"));
+            }
+        }
+        Some(self.corpus_path)
+    }
+}
+
+fn write_json(output_path: &str, record: &PaperRecord) {
+    let file = File::create(output_path).expect("Failed to create output file");
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, record)
+        .expect("Failed to write JSON");
+    writeln!(writer).expect("Failed to write JSON");
+    writer.flush().expect("Failed to flush JSON writer");
+}
+
+/// Appends one paper as a single line to a JSONL file.
+fn write_jsonl_record<W: Write>(writer: &mut W, record: &PaperRecord) {
+    serde_json::to_writer(&mut *writer, record)
+        .expect("Failed to write JSONL record");
+    writeln!(writer).expect("Failed to write JSONL record");
+}
+
+fn write_csv(
+    output_path: &str,
+    extracted_data: &ExtractionResult,
+    fields: &[SchemaField],
+) {
+    let file = File::create(output_path).expect("Failed to create output file");
+    let mut writer = Writer::from_writer(file);
+
+    writer
+        .write_record(CSV_HEADERS)
+        .expect("Failed to write headers");
+
+    for field in fields {
+        let field_data =
+            extracted_data.get(&field.field_name).unwrap_or_else(|| {
+                panic!(
+                    "Field {} not found in extraction result",
+                    field.field_name
+                )
+            });
+
+        let value = field_data
+            .value
+            .as_ref()
+            .map(value_text)
+            .unwrap_or_default();
+
+        let page = field_data
+            .page
+            .map(|page| page.to_string())
+            .unwrap_or_default();
+        let [xmin, ymin, xmax, ymax] =
+            field_data.bbox.map_or_else(Default::default, |bbox| {
+                [bbox.xmin, bbox.ymin, bbox.xmax, bbox.ymax]
+                    .map(|coordinate| coordinate.to_string())
+            });
+
+        let candidates = candidates_text(&field_data.candidates);
+
+        let row = vec![
+            field.field_name.clone(),
+            value,
+            field_data.match_type.clone(),
+            field_data.comment.clone().unwrap_or_default(),
+            page,
+            xmin,
+            ymin,
+            xmax,
+            ymax,
+            candidates,
+        ];
+
+        writer.write_record(&row).expect("Failed to write data row");
+    }
+
+    writer.flush().expect("Failed to flush CSV writer");
+}
+
+/// A field's candidates as a JSON array, empty when it has none.
+#[must_use]
+#[inline]
+pub fn candidates_text(candidates: &[Candidate]) -> String {
+    if candidates.is_empty() {
+        String::new()
+    } else {
+        serde_json::to_string(candidates).unwrap_or_default()
+    }
+}
+
+/// One row of the CSV written by an extraction run.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "imported unqualified by the review screen and the examples"
+)]
+#[derive(Debug, Clone, Deserialize)]
+#[non_exhaustive]
+pub struct OutputRow {
+    pub field_name: String,
+    pub value: String,
+    pub match_type: String,
+    pub comment: String,
+    pub page: String,
+    pub xmin: String,
+    pub ymin: String,
+    pub xmax: String,
+    pub ymax: String,
+    /// The field's candidates as a JSON array. Missing from CSVs written
+    /// before candidates existed.
+    #[serde(default)]
+    pub candidates: String,
+}
+
+impl OutputRow {
+    pub(crate) fn page_number(&self) -> Option<u32> {
+        self.page.parse().ok()
+    }
+
+    pub(crate) fn bbox(&self) -> Option<BoundingBox> {
+        let [xmin, ymin, xmax, ymax] =
+            [&self.xmin, &self.ymin, &self.xmax, &self.ymax]
+                .map(|coordinate| coordinate.parse::<f64>().ok());
+        Some(BoundingBox {
+            xmin: xmin?,
+            ymin: ymin?,
+            xmax: xmax?,
+            ymax: ymax?,
+        })
+    }
+}
+
+/// Reads the CSV of an extraction run.
+///
+/// # Errors
+///
+/// Fails when the file cannot be opened or a row does not parse.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "pairs with `write_csv`; a bare `read` would shadow `fs::read`"
+)]
+#[inline]
+pub fn read_output(path: &Path) -> Result<Vec<OutputRow>, String> {
+    Reader::from_path(path)
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))?
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::ExtractedField;
+    use crate::schema::SchemaKind;
+    use serde_json::{Value, json};
+
+    fn schema_field(name: &str) -> SchemaField {
+        SchemaField::new(name, SchemaKind::Number)
+    }
+
+    fn extracted(value: Value) -> ExtractedField {
+        ExtractedField {
+            value: Some(value),
+            match_type: "found".to_owned(),
+            comment: None,
+            page: None,
+            bbox: None,
+            warnings: Vec::new(),
+            candidates: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn record_keeps_types_and_schema_order() {
+        let sample_size: u32 = 24;
+        let fields = [schema_field("sample_size"), schema_field("arms")];
+        let results = ExtractionResult::from([
+            ("arms".to_owned(), extracted(json!(["graft", "control"]))),
+            (
+                "sample_size".to_owned(),
+                extracted(Value::from(sample_size)),
+            ),
+        ]);
+        let record = PaperRecord::new("smith.pdf", "abc123", &results, &fields);
+        let line = serde_json::to_string(&record).unwrap();
+
+        assert!(line.starts_with(
+            r#"{"paper":"smith.pdf","prompt_hash":"abc123","fields":{"sample_size":{"value":24,"#
+        ));
+        assert!(line.contains(r#""arms":{"value":["graft","control"],"#));
+        assert!(line.contains(r#""page":null"#));
+    }
+}
@@ -0,0 +1,365 @@
+use base64::{Engine as _, engine::general_purpose};
+use lopdf::{Dictionary, Document, Object};
+use std::collections::BTreeMap;
+
+/// Headings that mark the start of a paper's reference list, in the cases
+/// journals typeset them in.
+const REFERENCE_HEADINGS: [&str; 6] = [
+    "References",
+    "REFERENCES",
+    "Bibliography",
+    "BIBLIOGRAPHY",
+    "Literature Cited",
+    "LITERATURE CITED",
+];
+
+/// A subset of the pages of a PDF, re-encoded as a standalone document.
+///
+/// `pages` maps the 1-based page numbers of the trimmed document back to the
+/// page numbers of the original file.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "a bare `View` says nothing once imported"
+)]
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct PdfView {
+    pub data: Vec<u8>,
+    pub pages: Vec<u32>,
+}
+
+impl PdfView {
+    /// Maps a page number reported against this view back to the original
+    /// document. A page the view does not have maps to 0, which no document
+    /// has either, so validation drops the location with a warning instead
+    /// of taking it as that page of the whole document.
+    #[must_use]
+    #[inline]
+    pub fn original_page(&self, page: i64) -> i64 {
+        usize::try_from(page)
+            .ok()
+            .and_then(|number| number.checked_sub(1))
+            .and_then(|index| self.pages.get(index))
+            .map_or(0, |&original| i64::from(original))
+    }
+}
+
+/// Parses PDF bytes.
+///
+/// # Errors
+///
+/// Fails when `data` is not a readable PDF.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "a bare `load` says nothing once imported"
+)]
+#[inline]
+pub fn load_pdf(data: &[u8]) -> Result<Document, String> {
+    Document::load_mem(data).map_err(|e| format!("Failed to parse PDF: {e}"))
+}
+
+#[must_use]
+#[inline]
+pub fn page_count(document: &Document) -> u32 {
+    document.get_pages().keys().max().copied().unwrap_or(0)
+}
+
+/// The visible area of a page in PDF points, as given by its `MediaBox`.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct PageSize {
+    pub left: f64,
+    pub bottom: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl PageSize {
+    /// The `y` coordinate of the top edge in PDF user space.
+    #[expect(clippy::float_arithmetic, reason = "page geometry in points")]
+    #[must_use]
+    #[inline]
+    pub fn top(&self) -> f64 {
+        self.bottom + self.height
+    }
+}
+
+/// Reads the `MediaBox` of a page, following the `Parent` chain since the
+/// box is an inheritable attribute.
+#[inline]
+pub fn page_size(document: &Document, page: u32) -> Option<PageSize> {
+    let page_id = *document.get_pages().get(&page)?;
+    let mut dictionary = document.get_dictionary(page_id).ok()?;
+
+    loop {
+        if let Some(media_box) = media_box(document, dictionary) {
+            return Some(media_box);
+        }
+        let parent = dictionary.get(b"Parent").and_then(Object::as_reference);
+        dictionary = document.get_dictionary(parent.ok()?).ok()?;
+    }
+}
+
+/// Reads the size of every page, keyed by page number.
+#[must_use]
+#[inline]
+pub fn page_sizes(document: &Document) -> BTreeMap<u32, PageSize> {
+    document
+        .get_pages()
+        .into_keys()
+        .filter_map(|page| Some((page, page_size(document, page)?)))
+        .collect()
+}
+
+#[expect(clippy::float_arithmetic, reason = "page geometry in points")]
+fn media_box(document: &Document, dictionary: &Dictionary) -> Option<PageSize> {
+    let object = dictionary.get(b"MediaBox").ok()?;
+    let (_, resolved) = document.dereference(object).ok()?;
+    let corners: Vec<f64> = resolved
+        .as_array()
+        .ok()?
+        .iter()
+        .map(|value| value.as_float().map(f64::from))
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    let [x0, y0, x1, y1] = corners[..] else {
+        return None;
+    };
+    Some(PageSize {
+        left: x0.min(x1),
+        bottom: y0.min(y1),
+        width: (x1 - x0).abs(),
+        height: (y1 - y0).abs(),
+    })
+}
+
+/// Parses a page selection such as `1-12` or `1-3,5,9-10` into a sorted list
+/// of unique 1-based page numbers.
+///
+/// # Errors
+///
+/// Fails on a part that is not a page number or range, on page 0 and on
+/// a range that runs backwards.
+#[inline]
+pub fn parse_page_ranges(spec: &str) -> Result<Vec<u32>, String> {
+    let mut pages = Vec::new();
+
+    for part in spec.split(',').map(str::trim) {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let start: u32 = first
+            .trim()
+            .parse()
+            .map_err(|_e| format!("Invalid page range '{part}'"))?;
+        let end: u32 = last
+            .trim()
+            .parse()
+            .map_err(|_e| format!("Invalid page range '{part}'"))?;
+
+        if start == 0 || end < start {
+            return Err(format!(
+                "Invalid page range '{part}'. Pages start at 1 and ranges must be ascending"
+            ));
+        }
+
+        pages.extend(start..=end);
+    }
+
+    pages.sort_unstable();
+    pages.dedup();
+    Ok(pages)
+}
+
+/// Finds the page where the reference list begins, searching from the end of
+/// the document so that a "References" mention in the body is not mistaken for
+/// the heading.
+#[must_use]
+#[inline]
+pub fn find_references_page(document: &Document) -> Option<u32> {
+    document.get_pages().keys().rev().copied().find(|&page| {
+        document
+            .extract_text(&[page])
+            .is_ok_and(|text| starts_reference_list(&text))
+    })
+}
+
+/// Extracted text rarely keeps the heading on its own line, so a heading
+/// counts when it is directly followed by what looks like the first entry:
+/// a number, a bracket or a capitalised author name.
+fn starts_reference_list(text: &str) -> bool {
+    REFERENCE_HEADINGS.iter().any(|heading| {
+        text.match_indices(heading).any(|(index, _)| {
+            text.get(index.saturating_add(heading.len())..)
+                .and_then(|rest| {
+                    rest.trim_start_matches([' ', ':', '\n']).chars().next()
+                })
+                .is_some_and(|next| {
+                    next.is_ascii_digit() || next == '[' || next.is_uppercase()
+                })
+        })
+    })
+}
+
+/// Selects the pages to send to the model.
+///
+/// Starts from `pages` (or the whole document), and when `exclude_references`
+/// is set drops every page after the one where the reference list starts.
+/// That page itself is kept since it usually ends the discussion.
+///
+/// # Errors
+///
+/// Fails when a requested page is past the end of the document or no
+/// pages are left.
+#[inline]
+pub fn select_pages(
+    document: &Document,
+    pages: Option<&[u32]>,
+    exclude_references: bool,
+) -> Result<Vec<u32>, String> {
+    let total = page_count(document);
+    let mut selected: Vec<u32> = match pages {
+        Some(requested) => {
+            if let Some(&page) = requested.iter().find(|&&page| page > total) {
+                return Err(format!(
+                    "Page {page} is out of range (document has {total} pages)"
+                ));
+            }
+            requested.to_vec()
+        }
+        None => (1..=total).collect(),
+    };
+
+    if exclude_references
+        && let Some(references_page) = find_references_page(document)
+    {
+        selected.retain(|&page| page <= references_page);
+    }
+
+    if selected.is_empty() {
+        return Err("No pages selected".to_owned());
+    }
+
+    Ok(selected)
+}
+
+/// Builds a standalone PDF containing only `pages`, in document order.
+///
+/// # Errors
+///
+/// Fails when the trimmed PDF cannot be written.
+#[inline]
+pub fn build_view(
+    document: &Document,
+    pages: &[u32],
+) -> Result<PdfView, String> {
+    let mut trimmed = document.clone();
+    let removed: Vec<u32> = trimmed
+        .get_pages()
+        .into_keys()
+        .filter(|page| !pages.contains(page))
+        .collect();
+    trimmed.delete_pages(&removed);
+    trimmed.prune_objects();
+    trimmed.compress();
+
+    let mut data = Vec::new();
+    trimmed
+        .save_to(&mut data)
+        .map_err(|e| format!("Failed to write trimmed PDF: {e}"))?;
+
+    Ok(PdfView {
+        data,
+        pages: pages.to_vec(),
+    })
+}
+
+/// Splits the selected pages into windows of at most `window` pages and
+/// builds one view per window. A `window` of `None` keeps a single view.
+///
+/// # Errors
+///
+/// Fails when `window` is zero or a view cannot be built.
+#[inline]
+pub fn build_views(
+    data: &[u8],
+    document: &Document,
+    pages: &[u32],
+    window: Option<usize>,
+) -> Result<Vec<PdfView>, String> {
+    let whole_document = pages.len() == document.get_pages().len();
+
+    match window {
+        None if whole_document => Ok(vec![PdfView {
+            data: data.to_vec(),
+            pages: pages.to_vec(),
+        }]),
+        None => Ok(vec![build_view(document, pages)?]),
+        Some(0) => Err("Chunk size must be at least one page".to_owned()),
+        Some(size) => pages
+            .chunks(size)
+            .map(|chunk| build_view(document, chunk))
+            .collect(),
+    }
+}
+
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "the input is the raw PDF, not the parsed document"
+)]
+#[must_use]
+#[inline]
+pub fn pdf_to_base64(data: &[u8]) -> String {
+    let base64_data = general_purpose::STANDARD.encode(data);
+    format!("data:application/pdf;base64,{base64_data}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_range() {
+        assert_eq!(parse_page_ranges("1-4").unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn mixed_ranges_are_sorted_and_deduplicated() {
+        assert_eq!(
+            parse_page_ranges("5, 1-3,2-4").unwrap(),
+            vec![1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        for spec in ["0-3", "4-2", "a-b", "", "1-"] {
+            assert!(
+                parse_page_ranges(spec).is_err(),
+                "Should reject page range: {spec}"
+            );
+        }
+    }
+
+    #[test]
+    fn reference_headings() {
+        assert!(starts_reference_list("of the wrist.References1.Amadio PC"));
+        assert!(starts_reference_list("REFERENCES\n1. Ghormley RK"));
+        assert!(starts_reference_list("References Bronstein AJ, Trumble"));
+        assert!(starts_reference_list("References [1] Slagel BE"));
+        assert!(!starts_reference_list("References were checked by hand"));
+        assert!(!starts_reference_list("see the references section"));
+    }
+
+    #[test]
+    fn original_page_mapping() {
+        let view = PdfView {
+            data: Vec::new(),
+            pages: vec![3, 4, 9],
+        };
+        assert_eq!(view.original_page(1), 3);
+        assert_eq!(view.original_page(3), 9);
+        assert_eq!(view.original_page(0), 0);
+        assert_eq!(view.original_page(7), 0);
+        assert_eq!(view.original_page(-1), 0);
+    }
+}
@@ -0,0 +1,170 @@
+use crate::extraction::ExtractionResult;
+use crate::usage::Usage;
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Something that happened while extracting one paper. Windows and batches
+/// count from 0 and identify one request: a batch of schema fields sent with
+/// one page window of the document.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum ProgressEvent {
+    /// The pages to send have been chosen and the requests are queued.
+    PaperStarted {
+        page_count: u32,
+        pages_sent: usize,
+        windows: usize,
+        batches: usize,
+    },
+    /// A request has been given a concurrency permit and is being sent.
+    BatchStarted {
+        window: usize,
+        batch: usize,
+        fields: usize,
+    },
+    /// A request failed with an error worth retrying; `attempt` counts the
+    /// retries so far, starting at 1.
+    Retry {
+        window: usize,
+        batch: usize,
+        attempt: u32,
+        error: String,
+    },
+    /// A request came back and its answers have been merged.
+    BatchCompleted {
+        window: usize,
+        batch: usize,
+        fields: Vec<String>,
+        usage: Usage,
+        /// The merged answers for `fields`, including earlier windows.
+        #[serde(skip)]
+        results: ExtractionResult,
+    },
+    /// Part of a field's answer was invalid and has been dropped, such as a
+    /// page outside the document.
+    ValidationWarning { field: String, warning: String },
+    /// A field's bounding box was corrected and kept, such as swapped
+    /// corners or a box running off the page.
+    FieldRepaired { field: String, repair: String },
+    /// The first pass is done and the conditional fields have been checked.
+    /// Those whose condition holds are sent in `batches` more requests; the
+    /// others are `not_applicable`.
+    ConditionalPass {
+        applicable: usize,
+        not_applicable: usize,
+        batches: usize,
+    },
+    /// Every request has come back. `usage` totals all of them.
+    PaperDone {
+        found: usize,
+        inferred: usize,
+        not_found: usize,
+        not_applicable: usize,
+        ambiguous: usize,
+        usage: Usage,
+    },
+}
+
+impl ProgressEvent {
+    /// The merged answers carried by a completed batch.
+    #[expect(
+        clippy::pattern_type_mismatch,
+        clippy::wildcard_enum_match_arm,
+        reason = "the results are borrowed from the event; other events carry none"
+    )]
+    #[must_use]
+    #[inline]
+    pub const fn batch_results(&self) -> Option<&ExtractionResult> {
+        match self {
+            Self::BatchCompleted { results, .. } => Some(results),
+            _ => None,
+        }
+    }
+
+    /// Counts the answers in a finished extraction by match type.
+    #[must_use]
+    #[inline]
+    pub fn paper_done(results: &ExtractionResult, usage: Usage) -> Self {
+        let count = |match_type: &str| {
+            results
+                .values()
+                .filter(|field| field.match_type == match_type)
+                .count()
+        };
+        Self::PaperDone {
+            found: count("found"),
+            inferred: count("inferred"),
+            not_found: count("not_found"),
+            not_applicable: count("not_applicable"),
+            ambiguous: count("ambiguous"),
+            usage,
+        }
+    }
+}
+
+/// Where events go. Sending to a channel whose receiver is gone is not an
+/// error: nobody is listening.
+#[derive(Debug, Clone, Default)]
+pub struct Events {
+    sender: Option<UnboundedSender<ProgressEvent>>,
+}
+
+impl Events {
+    pub const fn new(sender: UnboundedSender<ProgressEvent>) -> Self {
+        Self {
+            sender: Some(sender),
+        }
+    }
+
+    pub fn emit(&self, event: ProgressEvent) {
+        if let Some(sender) = self.sender.as_ref() {
+            drop(sender.send(event));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::ExtractedField;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn events_serialize_as_tagged_json_lines() {
+        let field = ExtractedField {
+            value: None,
+            match_type: "not_found".to_owned(),
+            comment: None,
+            page: None,
+            bbox: None,
+            warnings: Vec::new(),
+            candidates: Vec::new(),
+        };
+        let results = ExtractionResult::from([("title".to_owned(), field)]);
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let events = Events::new(sender);
+
+        events.emit(ProgressEvent::BatchCompleted {
+            window: 0,
+            batch: 1,
+            fields: vec!["title".to_owned()],
+            usage: Usage::default(),
+            results: results.clone(),
+        });
+        events.emit(ProgressEvent::paper_done(&results, Usage::default()));
+        drop(events);
+
+        let mut lines = Vec::new();
+        while let Some(event) = receiver.blocking_recv() {
+            lines.push(serde_json::to_string(&event).unwrap());
+        }
+        assert_eq!(
+            lines,
+            [
+                r#"{"event":"batch_completed","window":0,"batch":1,"fields":["title"],"usage":{"prompt_tokens":0,"cached_tokens":0,"completion_tokens":0}}"#,
+                r#"{"event":"paper_done","found":0,"inferred":0,"not_found":1,"not_applicable":0,"ambiguous":0,"usage":{"prompt_tokens":0,"cached_tokens":0,"completion_tokens":0}}"#,
+            ]
+        );
+    }
+}
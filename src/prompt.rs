@@ -1,23 +1,253 @@
 use crate::schema::SchemaField;
+use sha2::{Digest as _, Sha256};
+use std::collections::BTreeMap;
 use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
 
 const PROMPT_TEMPLATE: &str = include_str!("prompt.md");
 
-pub fn build_prompt(fields: &[SchemaField]) -> String {
-    let mut fields_list = String::new();
+/// The answer format the JSON schema of every request enforces, described
+/// for the model.
+const OUTPUT_FORMAT: &str = include_str!("output_format.md");
+
+/// Placeholders a template may use.
+const VARIABLES: [&str; 6] = [
+    "FIELDS_LIST",
+    "FILENAME",
+    "DOMAIN_CONTEXT",
+    "OUTPUT_FORMAT",
+    "BATCH_INDEX",
+    "BATCH_COUNT",
+];
+
+/// Instructions sent after the document, with `{{VARIABLE}}` placeholders
+/// filled in for each request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplate {
+    text: String,
+}
+
+/// The values of a template's placeholders for one request.
+#[derive(Debug, Clone, Copy)]
+pub struct PromptVariables<'a> {
+    pub fields: &'a [SchemaField],
+    /// The paper's file name, empty when extracting from bytes alone.
+    pub filename: &'a str,
+    /// Background on the research area, empty when none was given.
+    pub domain_context: &'a str,
+    /// 1-based index of this request's batch of fields.
+    pub batch_index: usize,
+    pub batch_count: usize,
+    /// Rendered examples to list under each field, keyed by field name.
+    pub examples: &'a BTreeMap<String, String>,
+}
+
+impl Default for PromptTemplate {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            text: PROMPT_TEMPLATE.to_owned(),
+        }
+    }
+}
+
+impl PromptTemplate {
+    /// Checks that every `{{...}}` placeholder in `text` is a known
+    /// variable, so a typo fails before any request is sent.
+    ///
+    /// # Errors
+    ///
+    /// Fails on an unclosed or unknown placeholder, and when `{{FIELDS_LIST}}`
+    /// is missing.
+    #[inline]
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut rest = text;
+        while let Some((_, after)) = rest.split_once("{{") {
+            let (placeholder, remaining) = after
+                .split_once("}}")
+                .ok_or("Unclosed '{{' in prompt template")?;
+            let name = placeholder.trim();
+            if !VARIABLES.contains(&name) {
+                return Err(format!(
+                    "Unknown prompt template variable '{{{{{name}}}}}' (known: {})",
+                    VARIABLES.join(", ")
+                ));
+            }
+            rest = remaining;
+        }
+        if !text.contains("{{FIELDS_LIST}}") {
+            return Err(
+                "Prompt template must include {{FIELDS_LIST}}".to_owned()
+            );
+        }
+        Ok(Self {
+            text: text.to_owned(),
+        })
+    }
+
+    /// Reads and checks a prompt template file.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file cannot be read or the template is invalid.
+    #[inline]
+    pub fn from_path<P>(path: P) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        let text = fs::read_to_string(path.as_ref()).map_err(|e| {
+            format!(
+                "Failed to read prompt template {}: {e}",
+                path.as_ref().display()
+            )
+        })?;
+        Self::parse(&text)
+            .map_err(|e| format!("{}: {e}", path.as_ref().display()))
+    }
+
+    /// SHA-256 in hex of everything fixed for a run: the template text,
+    /// the output format instructions and the domain context. Recorded
+    /// with each run so results can be traced back to the instructions
+    /// that produced them.
+    #[must_use]
+    #[inline]
+    pub fn hash(&self, domain_context: &str) -> String {
+        let mut hasher = Sha256::new();
+        for part in [self.text.as_str(), OUTPUT_FORMAT, domain_context.trim()] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        hasher
+            .finalize()
+            .iter()
+            .fold(String::new(), |mut hex, byte| {
+                write!(&mut hex, "{byte:02x}").unwrap();
+                hex
+            })
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn render(&self, variables: &PromptVariables) -> String {
+        self.text
+            .replace(
+                "{{FIELDS_LIST}}",
+                &fields_list(variables.fields, variables.examples),
+            )
+            .replace("{{FILENAME}}", variables.filename)
+            .replace("{{DOMAIN_CONTEXT}}", variables.domain_context.trim())
+            .replace("{{OUTPUT_FORMAT}}", OUTPUT_FORMAT.trim_end())
+            .replace("{{BATCH_INDEX}}", &variables.batch_index.to_string())
+            .replace("{{BATCH_COUNT}}", &variables.batch_count.to_string())
+    }
+}
+
+fn fields_list(
+    fields: &[SchemaField],
+    examples: &BTreeMap<String, String>,
+) -> String {
+    let mut list = String::new();
     for field in fields {
         writeln!(
-            &mut fields_list,
+            &mut list,
             "- **{}**: {}",
             field.field_name, field.description
         )
         .unwrap();
         if field.infer {
-            fields_list.push_str(
+            list.push_str(
                 "  (This field should be inferred if not explicitly found)\n",
             );
         }
+        if !field.options.is_empty() {
+            writeln!(
+                &mut list,
+                "  (Answer with one of: {})",
+                field.options.join(", ")
+            )
+            .unwrap();
+        }
+        if let Some(limit) = field.max_candidates {
+            writeln!(
+                &mut list,
+                "  (List up to {limit} values the paper reports for this field in candidates)"
+            )
+            .unwrap();
+        }
+        if let Some(guidance) = field.guidance.as_deref() {
+            list.push_str("  Guidance:\n");
+            for line in guidance.lines() {
+                writeln!(&mut list, "    {line}").unwrap();
+            }
+        }
+        if let Some(lines) = examples.get(&field.field_name) {
+            list.push_str(lines);
+        }
+    }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::{MATCH_TYPES, SchemaKind};
+
+    #[test]
+    fn templates_fill_in_every_variable() {
+        let template = PromptTemplate::parse(
+            "{{DOMAIN_CONTEXT}}\nPaper {{FILENAME}}, batch {{BATCH_INDEX}} of {{BATCH_COUNT}}:\n{{FIELDS_LIST}}",
+        )
+        .unwrap();
+        let fields = [SchemaField {
+            description: "Sample size".to_owned(),
+            infer: true,
+            guidance: Some(
+                "Count randomised patients.\nIgnore dropouts.".to_owned(),
+            ),
+            ..SchemaField::new("n", SchemaKind::Number)
+        }];
+
+        let prompt = template.render(&PromptVariables {
+            fields: &fields,
+            filename: "smith2020.pdf",
+            domain_context: "Orthopaedic trials.\n",
+            batch_index: 2,
+            batch_count: 3,
+            examples: &BTreeMap::new(),
+        });
+        assert_eq!(
+            prompt,
+            "Orthopaedic trials.\nPaper smith2020.pdf, batch 2 of 3:\n- **n**: Sample size\n  (This field should be inferred if not explicitly found)\n  Guidance:\n    Count randomised patients.\n    Ignore dropouts.\n"
+        );
+        let hash = template.hash("Orthopaedic trials.");
+        assert_ne!(hash, PromptTemplate::default().hash("Orthopaedic trials."));
+        assert_ne!(hash, template.hash("Dental implants."));
+        assert_eq!(hash, template.hash("Orthopaedic trials.\n"));
+    }
+
+    #[test]
+    fn instructions_spell_match_types_like_the_schema() {
+        let mut rest = PROMPT_TEMPLATE;
+        let mut described = Vec::new();
+        while let Some((_, after)) = rest.split_once("match_type = \"") {
+            let (match_type, remaining) = after.split_once('"').unwrap();
+            assert!(MATCH_TYPES.contains(&match_type), "{match_type}");
+            described.push(match_type);
+            rest = remaining;
+        }
+        for match_type in MATCH_TYPES {
+            assert!(described.contains(&match_type), "{match_type}");
+        }
+        assert!(OUTPUT_FORMAT.contains(&MATCH_TYPES.join("|")));
     }
 
-    PROMPT_TEMPLATE.replace("{{FIELDS_LIST}}", &fields_list)
+    #[test]
+    fn unknown_variables_are_rejected() {
+        let typo = PromptTemplate::parse("{{FIELDS_LIST}} {{FILE_NAME}}");
+        assert!(typo.unwrap_err().contains("FILE_NAME"));
+
+        let no_fields = PromptTemplate::parse("Extract {{FILENAME}}");
+        assert!(no_fields.unwrap_err().contains("{{FIELDS_LIST}}"));
+    }
 }
@@ -0,0 +1,178 @@
+use serde_json::Value;
+use std::sync::{Mutex, PoisonError};
+use tokio::time::{Duration, Instant, sleep};
+
+const SECONDS_PER_MINUTE: f64 = 60.0;
+
+/// Rough number of characters per token for English text.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Rough token cost of one page, whether sent as a PDF page or an image.
+const TOKENS_PER_PAGE: usize = 1_500;
+
+/// Client-side limits for one provider, so a large run waits its turn
+/// instead of failing with rate limit errors. `None` means unlimited.
+#[expect(
+    clippy::exhaustive_structs,
+    reason = "callers build it from their own settings; both limits are set"
+)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+}
+
+/// Token buckets for requests and tokens, shared by every request an
+/// extractor sends.
+#[derive(Debug)]
+pub struct RateLimiter {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        Self {
+            requests: limits.requests_per_minute.map(TokenBucket::per_minute),
+            tokens: limits.tokens_per_minute.map(TokenBucket::per_minute),
+        }
+    }
+
+    /// Waits until one request costing `tokens` fits within both limits.
+    pub async fn acquire(&self, tokens: usize) {
+        if let Some(bucket) = self.requests.as_ref() {
+            bucket.take(1).await;
+        }
+        if let Some(bucket) = self.tokens.as_ref() {
+            bucket.take(tokens).await;
+        }
+    }
+}
+
+/// A bucket that refills continuously up to one minute's allowance.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    available: f64,
+    updated: Instant,
+}
+
+#[expect(clippy::float_arithmetic, reason = "continuous refill rate")]
+impl TokenBucket {
+    fn per_minute(limit: u32) -> Self {
+        let capacity = f64::from(limit.max(1));
+        Self {
+            capacity,
+            state: Mutex::new(BucketState {
+                available: capacity,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `amount` from the bucket, sleeping until enough has refilled.
+    /// Amounts larger than the bucket only wait for a full bucket.
+    async fn take(&self, amount: usize) {
+        let wanted = f64::from(u32::try_from(amount).unwrap_or(u32::MAX))
+            .min(self.capacity);
+        let per_second = self.capacity / SECONDS_PER_MINUTE;
+        loop {
+            let wait = {
+                let mut state =
+                    self.state.lock().unwrap_or_else(PoisonError::into_inner);
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.updated).as_secs_f64();
+                state.available = elapsed
+                    .mul_add(per_second, state.available)
+                    .min(self.capacity);
+                state.updated = now;
+                if state.available >= wanted {
+                    state.available -= wanted;
+                    return;
+                }
+                (wanted - state.available) / per_second
+            };
+            sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
+
+/// Estimates the tokens of a piece of English text.
+pub const fn estimate_text_tokens(text: &str) -> usize {
+    text.len().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Estimates the tokens one request uses: its text, its pages and the
+/// expected answer. Only used to pace requests, so it errs on the
+/// side of simplicity.
+pub fn estimate_request_tokens(
+    prompt: &str,
+    document_parts: &[Value],
+    pages: usize,
+    output_tokens: usize,
+) -> usize {
+    let text: usize = document_parts
+        .iter()
+        .filter_map(|part| part.get("text").and_then(Value::as_str))
+        .map(str::len)
+        .sum();
+    let has_pages = document_parts.iter().any(|part| {
+        part.get("file").is_some() || part.get("image_url").is_some()
+    });
+
+    let input = prompt
+        .len()
+        .saturating_add(text)
+        .checked_div(CHARS_PER_TOKEN)
+        .unwrap_or_default();
+    let page_tokens = if has_pages {
+        pages.saturating_mul(TOKENS_PER_PAGE)
+    } else {
+        0
+    };
+    input
+        .saturating_add(page_tokens)
+        .saturating_add(output_tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test(start_paused = true)]
+    async fn requests_wait_for_the_bucket_to_refill() {
+        let limiter = RateLimiter::new(RateLimits {
+            requests_per_minute: Some(2),
+            tokens_per_minute: None,
+        });
+        let start = Instant::now();
+        limiter.acquire(0).await;
+        limiter.acquire(0).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        limiter.acquire(0).await;
+        let waited = start.elapsed();
+        assert!(waited >= Duration::from_secs(29), "waited {waited:?}");
+        assert!(waited <= Duration::from_secs(31), "waited {waited:?}");
+    }
+
+    #[test]
+    fn token_estimate_counts_text_pages_and_answer() {
+        let text = [json!({"type": "text", "text": "a".repeat(400)})];
+        assert_eq!(estimate_request_tokens("", &text, 3, 0), 100);
+
+        let pdf = [json!({"type": "file", "file": {"file_data": "..."}})];
+        let pages: usize = 2;
+        let output: usize = 500;
+        assert_eq!(
+            estimate_request_tokens("", &pdf, pages, output),
+            pages * TOKENS_PER_PAGE + output
+        );
+    }
+}
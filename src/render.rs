@@ -0,0 +1,67 @@
+use std::io::Write as _;
+use std::process::{Command, Stdio};
+
+/// Points per inch in PDF user space.
+pub const POINTS_PER_INCH: u32 = 72;
+
+/// Renders one page of a PDF to PNG at the given resolution.
+///
+/// Rasterizing is delegated to `pdftoppm` from poppler-utils, which must be
+/// on the `PATH`. The PDF is piped through stdin so that trimmed documents
+/// never touch the disk.
+pub fn render_page_png(
+    pdf: &[u8],
+    page: u32,
+    dpi: u32,
+) -> Result<Vec<u8>, String> {
+    let page_arg = page.to_string();
+    let mut child = Command::new("pdftoppm")
+        .args(["-png", "-singlefile", "-r", &dpi.to_string()])
+        .args(["-f", &page_arg, "-l", &page_arg, "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            format!(
+                "Failed to run pdftoppm (install poppler-utils to render pages): {e}"
+            )
+        })?;
+
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| "Failed to open pdftoppm stdin".to_owned())?
+        .write_all(pdf)
+        .map_err(|e| format!("Failed to send PDF to pdftoppm: {e}"))?;
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to read pdftoppm output: {e}"))?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "pdftoppm failed to render page {page}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+/// Converts a length in pixels of an image rendered at `dpi` to PDF points.
+#[expect(clippy::float_arithmetic, reason = "pixel to point scaling")]
+pub fn pixels_to_points(pixels: f64, dpi: u32) -> f64 {
+    pixels * f64::from(POINTS_PER_INCH) / f64::from(dpi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_conversion() {
+        assert!((pixels_to_points(150.0, 150) - 72.0).abs() < f64::EPSILON);
+        assert!((pixels_to_points(300.0, 72) - 300.0).abs() < f64::EPSILON);
+    }
+}
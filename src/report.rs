@@ -0,0 +1,530 @@
+use crate::extraction::{ExtractedField, ExtractionResult, value_text};
+use crate::pdf::{PageSize, page_size};
+use crate::render::render_page_png;
+use crate::schema::SchemaField;
+use crate::text::{evidence_text, page_runs};
+use base64::{Engine as _, engine::general_purpose};
+use lopdf::Document;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write as _};
+
+/// Resolution of the page thumbnails embedded in the report. Low enough to
+/// keep a corpus report small, high enough to read the boxed text.
+const THUMBNAIL_DPI: u32 = 60;
+
+/// Stroke width of the boxes drawn over thumbnails, in points.
+const BOX_STROKE: f64 = 1.5;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 1.5em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; vertical-align: top; text-align: left; }
+th { background: #f2f2f2; }
+td.evidence { font-family: monospace; white-space: pre-wrap; max-width: 30em; }
+tr.warned { background: #fff4e0; }
+ul.warnings { margin: 0; padding-left: 1.2em; color: #a05a00; }
+.match { padding: 1px 6px; border-radius: 3px; white-space: nowrap; }
+.match-found { background: #c8efb8; }
+.match-inferred { background: #ffe2a3; }
+.match-ambiguous { background: #ddd0f2; }
+.match-not-applicable { background: #e4e4e4; }
+.match-other { background: #f5c1c1; }
+.bar { background: #e5e5e5; width: 10em; height: 0.8em; }
+.bar div { background: #5a9; height: 100%; }
+.candidates { margin: 0.3em 0 0; padding-left: 1.2em; color: #555; font-size: 0.9em; }
+figure { display: inline-block; margin: 0 1em 1em 0; }
+figure svg { width: 320px; height: auto; border: 1px solid #999; }
+";
+
+/// A rendered page, base64 encoded, with the page size it was drawn from.
+struct Thumbnail {
+    png_base64: String,
+    size: PageSize,
+}
+
+/// Everything the report shows about one paper.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "one paper's part of the report, not the whole report"
+)]
+pub struct PaperReport {
+    name: String,
+    results: ExtractionResult,
+    evidence: BTreeMap<String, String>,
+    thumbnails: BTreeMap<u32, Thumbnail>,
+    /// Why the thumbnails are missing, when pages could not be rendered.
+    warning: Option<String>,
+}
+
+impl PaperReport {
+    /// Collects the evidence text under every located value and renders a
+    /// thumbnail of each page that has one. Thumbnails are skipped when
+    /// pages cannot be rendered, and the reason is kept for `warning`.
+    #[must_use]
+    #[inline]
+    pub fn new(
+        name: &str,
+        pdf_data: &[u8],
+        document: &Document,
+        results: ExtractionResult,
+    ) -> Self {
+        let mut evidence = BTreeMap::new();
+        let mut runs_by_page = BTreeMap::new();
+        for (field_name, field) in &results {
+            let (Some(page), Some(bbox)) = (field.page, field.bbox) else {
+                continue;
+            };
+            let runs = runs_by_page.entry(page).or_insert_with(|| {
+                page_runs(document, page).unwrap_or_default()
+            });
+            let text = evidence_text(runs, &bbox);
+            if !text.is_empty() {
+                evidence.insert(field_name.clone(), text);
+            }
+        }
+
+        let located: BTreeSet<u32> = results
+            .values()
+            .filter(|field| field.bbox.is_some())
+            .filter_map(|field| field.page)
+            .collect();
+        let mut thumbnails = BTreeMap::new();
+        let mut warning = None;
+        for page in located {
+            let Some(size) = page_size(document, page) else {
+                continue;
+            };
+            match render_page_png(pdf_data, page, THUMBNAIL_DPI) {
+                Ok(png) => {
+                    let png_base64 = general_purpose::STANDARD.encode(png);
+                    thumbnails.insert(page, Thumbnail { png_base64, size });
+                }
+                Err(e) => {
+                    warning = Some(format!("Report thumbnails omitted: {e}"));
+                    break;
+                }
+            }
+        }
+
+        Self {
+            name: name.to_owned(),
+            results,
+            evidence,
+            thumbnails,
+            warning,
+        }
+    }
+
+    /// Why the thumbnails are missing, when pages could not be rendered.
+    #[must_use]
+    #[inline]
+    pub fn warning(&self) -> Option<&str> {
+        self.warning.as_deref()
+    }
+}
+
+/// Renders a self-contained HTML report for one or more papers. With more
+/// than one paper, a corpus overview with each field's fill rate comes
+/// first.
+///
+/// # Errors
+///
+/// Fails when the HTML cannot be written.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "a bare `render` would be confused with page rendering"
+)]
+#[inline]
+pub fn render_report(
+    fields: &[SchemaField],
+    papers: &[PaperReport],
+) -> Result<String, String> {
+    let mut html = String::new();
+    write_report(&mut html, fields, papers).map_err(|e| e.to_string())?;
+    Ok(html)
+}
+
+fn write_report(
+    html: &mut String,
+    fields: &[SchemaField],
+    papers: &[PaperReport],
+) -> fmt::Result {
+    writeln!(html, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>")?;
+    writeln!(html, "<meta charset=\"utf-8\">")?;
+    writeln!(html, "<title>Extraction report</title>")?;
+    writeln!(html, "<style>\n{STYLE}</style>\n</head>\n<body>")?;
+    writeln!(html, "<h1>Extraction report</h1>")?;
+
+    if papers.len() > 1 {
+        write_corpus(html, fields, papers)?;
+    }
+    for (index, paper) in papers.iter().enumerate() {
+        write_paper(html, index, fields, paper)?;
+    }
+
+    writeln!(html, "</body>\n</html>")
+}
+
+fn is_filled(field: &ExtractedField) -> bool {
+    matches!(
+        field.match_type.as_str(),
+        "found" | "inferred" | "ambiguous"
+    )
+}
+
+fn write_corpus(
+    html: &mut String,
+    fields: &[SchemaField],
+    papers: &[PaperReport],
+) -> fmt::Result {
+    writeln!(html, "<section id=\"corpus\">")?;
+    writeln!(html, "<h2>Corpus ({} papers)</h2>", papers.len())?;
+
+    writeln!(html, "<h3>Fill rate</h3>\n<table>")?;
+    writeln!(
+        html,
+        "<tr><th>Field</th><th>Found</th><th>Inferred</th><th>Ambiguous</th><th>Fill rate</th><th></th></tr>"
+    )?;
+    for schema_field in fields {
+        let answers: Vec<&ExtractedField> = papers
+            .iter()
+            .filter_map(|paper| paper.results.get(&schema_field.field_name))
+            .collect();
+        let count = |match_type: &str| {
+            answers
+                .iter()
+                .filter(|field| field.match_type == match_type)
+                .count()
+        };
+        let found = count("found");
+        let inferred = count("inferred");
+        let ambiguous = count("ambiguous");
+        let filled = answers.iter().filter(|field| is_filled(field)).count();
+        let percent = filled
+            .saturating_mul(100)
+            .checked_div(papers.len())
+            .unwrap_or(0);
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{found}</td><td>{inferred}</td><td>{ambiguous}</td>\
+             <td>{filled}/{} ({percent}%)</td>\
+             <td><div class=\"bar\"><div style=\"width: {percent}%\"></div></div></td></tr>",
+            escape(&schema_field.field_name),
+            papers.len(),
+        )?;
+    }
+    writeln!(html, "</table>")?;
+
+    writeln!(
+        html,
+        "<h3>Match types by paper</h3>\n<table>\n<tr><th>Field</th>"
+    )?;
+    for (index, paper) in papers.iter().enumerate() {
+        writeln!(
+            html,
+            "<th><a href=\"#paper-{index}\">{}</a></th>",
+            escape(&paper.name)
+        )?;
+    }
+    writeln!(html, "</tr>")?;
+    for schema_field in fields {
+        write!(html, "<tr><td>{}</td>", escape(&schema_field.field_name))?;
+        for paper in papers {
+            match paper.results.get(&schema_field.field_name) {
+                Some(field) => {
+                    write!(
+                        html,
+                        "<td>{}</td>",
+                        match_badge(&field.match_type)
+                    )?;
+                }
+                None => write!(html, "<td></td>")?,
+            }
+        }
+        writeln!(html, "</tr>")?;
+    }
+    writeln!(html, "</table>\n</section>")
+}
+
+fn write_paper(
+    html: &mut String,
+    index: usize,
+    fields: &[SchemaField],
+    paper: &PaperReport,
+) -> fmt::Result {
+    let warned = paper
+        .results
+        .values()
+        .filter(|field| !field.warnings.is_empty())
+        .count();
+
+    writeln!(html, "<section id=\"paper-{index}\">")?;
+    writeln!(html, "<h2>{}</h2>", escape(&paper.name))?;
+    if warned > 0 {
+        writeln!(
+            html,
+            "<p>{warned} field(s) had their location corrected during validation; they are highlighted below.</p>"
+        )?;
+    }
+
+    writeln!(html, "<table>")?;
+    writeln!(
+        html,
+        "<tr><th>Field</th><th>Value</th><th>Match type</th><th>Comment</th>\
+         <th>Evidence</th><th>Page</th><th>Warnings</th></tr>"
+    )?;
+    for schema_field in fields {
+        let name = &schema_field.field_name;
+        let Some(field) = paper.results.get(name) else {
+            writeln!(
+                html,
+                "<tr><td>{}</td><td colspan=\"6\">No answer</td></tr>",
+                escape(name)
+            )?;
+            continue;
+        };
+
+        let class = if field.warnings.is_empty() {
+            ""
+        } else {
+            " class=\"warned\""
+        };
+        let mut value =
+            escape(&field.value.as_ref().map(value_text).unwrap_or_default());
+        if !field.candidates.is_empty() {
+            write!(&mut value, "<ul class=\"candidates\">")?;
+            for candidate in &field.candidates {
+                write!(
+                    &mut value,
+                    "<li>{}",
+                    escape(&value_text(&candidate.value))
+                )?;
+                if let Some(page) = candidate.page {
+                    write!(
+                        &mut value,
+                        " (page {})",
+                        page_link(index, page, paper)
+                    )?;
+                }
+                if let Some(evidence) = candidate.evidence.as_deref() {
+                    write!(&mut value, ": <q>{}</q>", escape(evidence))?;
+                }
+                write!(&mut value, "</li>")?;
+            }
+            write!(&mut value, "</ul>")?;
+        }
+        let page = field
+            .page
+            .map(|number| page_link(index, number, paper))
+            .unwrap_or_default();
+        write!(
+            html,
+            "<tr{class}><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td class=\"evidence\">{}</td><td>{page}</td><td>",
+            escape(name),
+            value,
+            match_badge(&field.match_type),
+            escape(field.comment.as_deref().unwrap_or_default()),
+            escape(paper.evidence.get(name).map_or("", String::as_str)),
+        )?;
+        if !field.warnings.is_empty() {
+            write!(html, "<ul class=\"warnings\">")?;
+            for warning in &field.warnings {
+                write!(html, "<li>{}</li>", escape(warning))?;
+            }
+            write!(html, "</ul>")?;
+        }
+        writeln!(html, "</td></tr>")?;
+    }
+    writeln!(html, "</table>")?;
+
+    for (&page, thumbnail) in &paper.thumbnails {
+        write_thumbnail(html, index, page, thumbnail, fields, &paper.results)?;
+    }
+    writeln!(html, "</section>")
+}
+
+/// A page number, linked to its thumbnail when there is one.
+fn page_link(index: usize, page: u32, paper: &PaperReport) -> String {
+    if paper.thumbnails.contains_key(&page) {
+        format!("<a href=\"#paper-{index}-page-{page}\">{page}</a>")
+    } else {
+        page.to_string()
+    }
+}
+
+/// Draws a page thumbnail as an SVG in page points, so boxes can be placed
+/// with the coordinates from the extraction as they are.
+#[expect(clippy::float_arithmetic, reason = "box geometry in points")]
+fn write_thumbnail(
+    html: &mut String,
+    index: usize,
+    page: u32,
+    thumbnail: &Thumbnail,
+    fields: &[SchemaField],
+    results: &ExtractionResult,
+) -> fmt::Result {
+    let PageSize { width, height, .. } = thumbnail.size;
+    writeln!(html, "<figure id=\"paper-{index}-page-{page}\">")?;
+    writeln!(
+        html,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {width:.1} {height:.1}\">"
+    )?;
+    writeln!(
+        html,
+        "<image width=\"{width:.1}\" height=\"{height:.1}\" href=\"data:image/png;base64,{}\"/>",
+        thumbnail.png_base64
+    )?;
+    for schema_field in fields {
+        let Some(field) = results.get(&schema_field.field_name) else {
+            continue;
+        };
+        if let Some(bbox) = field.bbox.filter(|_| field.page == Some(page)) {
+            writeln!(
+                html,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+                 fill=\"none\" stroke=\"{}\" stroke-width=\"{BOX_STROKE}\">\
+                 <title>{}</title></rect>",
+                bbox.xmin,
+                bbox.ymin,
+                bbox.xmax - bbox.xmin,
+                bbox.ymax - bbox.ymin,
+                box_colour(&field.match_type),
+                escape(&schema_field.field_name),
+            )?;
+        }
+        // Other candidates are dashed, so the conflict shows on the page.
+        for candidate in &field.candidates {
+            let Some(bbox) = candidate.bbox.filter(|&candidate_box| {
+                candidate.page == Some(page)
+                    && field.bbox != Some(candidate_box)
+            }) else {
+                continue;
+            };
+            writeln!(
+                html,
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" \
+                 fill=\"none\" stroke=\"{}\" stroke-width=\"{BOX_STROKE}\" \
+                 stroke-dasharray=\"4 2\"><title>{}: {}</title></rect>",
+                bbox.xmin,
+                bbox.ymin,
+                bbox.xmax - bbox.xmin,
+                bbox.ymax - bbox.ymin,
+                box_colour("ambiguous"),
+                escape(&schema_field.field_name),
+                escape(&value_text(&candidate.value)),
+            )?;
+        }
+    }
+    writeln!(html, "</svg>")?;
+    writeln!(html, "<figcaption>Page {page}</figcaption>\n</figure>")
+}
+
+fn match_class(match_type: &str) -> &'static str {
+    match match_type {
+        "found" => "match-found",
+        "inferred" => "match-inferred",
+        "ambiguous" => "match-ambiguous",
+        "not_applicable" => "match-not-applicable",
+        _ => "match-other",
+    }
+}
+
+fn box_colour(match_type: &str) -> &'static str {
+    match match_type {
+        "found" => "#2a8a2a",
+        "inferred" => "#d08a00",
+        "ambiguous" => "#7a4fc0",
+        _ => "#c03030",
+    }
+}
+
+fn match_badge(match_type: &str) -> String {
+    format!(
+        "<span class=\"match {}\">{}</span>",
+        match_class(match_type),
+        escape(match_type)
+    )
+}
+
+/// Escapes text for use in HTML element content and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::SchemaKind;
+    use serde_json::Value;
+
+    fn schema_field(name: &str) -> SchemaField {
+        SchemaField::new(name, SchemaKind::Text)
+    }
+
+    fn paper(name: &str, match_type: &str, warnings: &[&str]) -> PaperReport {
+        let field = ExtractedField {
+            value: Some(Value::from("<12 mm>")),
+            match_type: match_type.to_owned(),
+            comment: None,
+            page: None,
+            bbox: None,
+            warnings: warnings.iter().map(|&w| w.to_owned()).collect(),
+            candidates: Vec::new(),
+        };
+        PaperReport {
+            name: name.to_owned(),
+            results: BTreeMap::from([("defect_size".to_owned(), field)]),
+            evidence: BTreeMap::new(),
+            thumbnails: BTreeMap::new(),
+            warning: None,
+        }
+    }
+
+    #[test]
+    fn escapes_markup() {
+        assert_eq!(
+            escape("a < b & \"c\" > 'd'"),
+            "a &lt; b &amp; &quot;c&quot; &gt; &#39;d&#39;"
+        );
+    }
+
+    #[test]
+    fn single_paper_flags_warnings() {
+        let papers =
+            [paper("smith.pdf", "found", &["bounding box was clamped"])];
+        let html =
+            render_report(&[schema_field("defect_size")], &papers).unwrap();
+        assert!(!html.contains("id=\"corpus\""));
+        assert!(html.contains("&lt;12 mm&gt;"));
+        assert!(html.contains("class=\"warned\""));
+        assert!(html.contains("bounding box was clamped"));
+    }
+
+    #[test]
+    fn corpus_reports_fill_rate() {
+        let papers = [
+            paper("a.pdf", "found", &[]),
+            paper("b.pdf", "inferred", &[]),
+            paper("c.pdf", "not_found", &[]),
+            paper("d.pdf", "ambiguous", &[]),
+        ];
+        let html =
+            render_report(&[schema_field("defect_size")], &papers).unwrap();
+        assert!(html.contains("id=\"corpus\""));
+        assert!(html.contains(
+            "<td>defect_size</td><td>1</td><td>1</td><td>1</td><td>3/4 (75%)</td>"
+        ));
+    }
+}
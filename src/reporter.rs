@@ -0,0 +1,163 @@
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressStyle};
+use paper_extract::{ProgressEvent, Usage};
+use serde_json::{Value, json};
+
+/// Template for the per-paper progress bar.
+const BAR_TEMPLATE: &str = "{bar:30} {pos}/{len} batches {elapsed} {msg}";
+
+/// How progress is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Readable lines with a progress bar on a terminal.
+    Text,
+    /// One JSON object per line, for CI and log aggregation.
+    Json,
+}
+
+/// Renders extraction progress as text with a progress bar per paper, or as
+/// JSON lines tagged with the paper's name.
+pub struct Reporter {
+    format: LogFormat,
+    bar: Option<ProgressBar>,
+    usage: Usage,
+}
+
+impl Reporter {
+    pub fn new(format: LogFormat) -> Self {
+        Self {
+            format,
+            bar: None,
+            usage: Usage::default(),
+        }
+    }
+
+    /// Writes a line of text, or a `message` event in JSON mode.
+    pub fn message(&self, text: &str) {
+        match self.format {
+            LogFormat::Text => self.line(text),
+            LogFormat::Json => {
+                println!("{}", json!({"event": "message", "message": text}));
+            }
+        }
+    }
+
+    /// Writes a line above the progress bar, if one is showing. A hidden bar
+    /// (when not on a terminal) drops its lines, so those are printed as is.
+    fn line(&self, text: &str) {
+        match self.bar.as_ref().filter(|bar| !bar.is_hidden()) {
+            Some(bar) => bar.println(text),
+            None => println!("{text}"),
+        }
+    }
+
+    /// Writes the token use of the whole run.
+    pub fn finish(&self) {
+        match self.format {
+            LogFormat::Text => {
+                println!("Run total: {}", usage_line(&self.usage));
+            }
+            LogFormat::Json => {
+                println!(
+                    "{}",
+                    json!({"event": "run_done", "usage": self.usage})
+                );
+            }
+        }
+    }
+
+    pub fn event(&mut self, paper: &str, event: ProgressEvent) {
+        if let ProgressEvent::PaperDone { usage, .. } = event {
+            self.usage.include(usage);
+        }
+        if self.format == LogFormat::Json {
+            let mut line = serde_json::to_value(&event).unwrap_or(Value::Null);
+            if let Some(object) = line.as_object_mut() {
+                object.insert("paper".to_owned(), Value::from(paper));
+            }
+            println!("{line}");
+            return;
+        }
+
+        match event {
+            ProgressEvent::PaperStarted {
+                page_count,
+                pages_sent,
+                windows,
+                batches,
+            } => {
+                println!(
+                    "Sending {pages_sent} of {page_count} pages in {windows} window(s), {batches} batches"
+                );
+                let bar = ProgressBar::new(u64::try_from(batches).unwrap_or(0));
+                if let Ok(style) = ProgressStyle::with_template(BAR_TEMPLATE) {
+                    bar.set_style(style);
+                }
+                self.bar = Some(bar);
+            }
+            ProgressEvent::Retry {
+                batch,
+                attempt,
+                error,
+                ..
+            } => self.line(&format!(
+                "Retrying batch {} (attempt {attempt}): {error}",
+                batch.saturating_add(1)
+            )),
+            ProgressEvent::BatchCompleted { batch, fields, .. } => {
+                if let Some(bar) = self.bar.as_ref() {
+                    bar.set_message(format!(
+                        "batch {} ({} fields)",
+                        batch.saturating_add(1),
+                        fields.len()
+                    ));
+                    bar.inc(1);
+                }
+            }
+            ProgressEvent::ValidationWarning { field, warning } => {
+                self.line(&format!("Warning: field '{field}': {warning}"));
+            }
+            ProgressEvent::FieldRepaired { field, repair } => {
+                self.line(&format!("Repaired field '{field}': {repair}"));
+            }
+            ProgressEvent::ConditionalPass {
+                applicable,
+                not_applicable,
+                batches,
+            } => {
+                if let Some(bar) = self.bar.as_ref() {
+                    bar.inc_length(u64::try_from(batches).unwrap_or(0));
+                }
+                self.line(&format!(
+                    "Conditional fields: {applicable} apply, {not_applicable} not applicable"
+                ));
+            }
+            ProgressEvent::PaperDone {
+                found,
+                inferred,
+                not_found,
+                not_applicable,
+                ambiguous,
+                usage,
+            } => {
+                if let Some(bar) = self.bar.take() {
+                    bar.finish_and_clear();
+                }
+                println!(
+                    "All batches completed: {found} found, {inferred} inferred, {not_found} not found, {not_applicable} not applicable, {ambiguous} ambiguous"
+                );
+                println!("{}", usage_line(&usage));
+            }
+            // Batch starts, and events added after this reporter, are not
+            // shown.
+            ProgressEvent::BatchStarted { .. } | _ => {}
+        }
+    }
+}
+
+fn usage_line(usage: &Usage) -> String {
+    format!(
+        "{} prompt tokens ({} from cache), {} completion tokens",
+        usage.prompt_tokens, usage.cached_tokens, usage.completion_tokens
+    )
+}
@@ -0,0 +1,521 @@
+use crate::extraction::Candidate;
+use crate::output::{OutputRow, read_output};
+use crate::pdf::load_pdf;
+use crate::text::{evidence_text, page_runs};
+use chrono::{SecondsFormat, Utc};
+use csv::{Reader, Writer};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const HELP: &str = "[a/Enter] accept  [e] edit  [n] not found  \
+[\u{2190}/\u{2192}] previous/next  [q] quit";
+
+const EDIT_HELP: &str = "[Enter] save  [Esc] cancel";
+
+/// One line per candidate with its page and the text it was read from.
+fn candidate_lines(row: &OutputRow) -> Vec<String> {
+    let Ok(candidates) =
+        serde_json::from_str::<Vec<Candidate>>(&row.candidates)
+    else {
+        return vec![format!("  {}", row.candidates)];
+    };
+    candidates
+        .iter()
+        .map(|candidate| match candidate.evidence.as_deref() {
+            Some(evidence) => {
+                format!("  {}: \"{evidence}\"", candidate.summary())
+            }
+            None => format!("  {}", candidate.summary()),
+        })
+        .collect()
+}
+
+fn location(row: &OutputRow) -> String {
+    match (row.page_number(), row.bbox()) {
+        (Some(page), Some(bbox)) => format!(
+            "page {page}, box ({:.0}, {:.0})-({:.0}, {:.0})",
+            bbox.xmin, bbox.ymin, bbox.xmax, bbox.ymax
+        ),
+        (Some(page), None) => format!("page {page}"),
+        (None, _) => "none".to_owned(),
+    }
+}
+
+/// What the reviewer did with a machine-extracted value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Decision {
+    Accepted,
+    Edited,
+    NotFound,
+}
+
+/// A reviewed field as stored in the reviewed CSV: the final value and
+/// location, the machine's original answer, and who decided when.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[non_exhaustive]
+pub struct ReviewedRow {
+    pub field_name: String,
+    pub value: String,
+    pub match_type: String,
+    pub comment: String,
+    pub page: String,
+    pub xmin: String,
+    pub ymin: String,
+    pub xmax: String,
+    pub ymax: String,
+    pub decision: Decision,
+    pub machine_value: String,
+    pub machine_match_type: String,
+    pub reviewer: String,
+    pub reviewed_at: String,
+}
+
+impl ReviewedRow {
+    /// Records a decision on `row`. A field marked not found loses its value
+    /// and location, and an edited value is the reviewer's reading of the
+    /// paper, so it counts as found.
+    #[must_use]
+    #[inline]
+    pub fn new(
+        row: &OutputRow,
+        decision: Decision,
+        value: String,
+        reviewer: &str,
+    ) -> Self {
+        let not_found = decision == Decision::NotFound;
+        let located = |text: &String| {
+            if not_found {
+                String::new()
+            } else {
+                text.clone()
+            }
+        };
+        Self {
+            field_name: row.field_name.clone(),
+            value: if not_found { String::new() } else { value },
+            match_type: match decision {
+                Decision::NotFound => "not_found".to_owned(),
+                Decision::Edited => "found".to_owned(),
+                Decision::Accepted => row.match_type.clone(),
+            },
+            comment: row.comment.clone(),
+            page: located(&row.page),
+            xmin: located(&row.xmin),
+            ymin: located(&row.ymin),
+            xmax: located(&row.xmax),
+            ymax: located(&row.ymax),
+            decision,
+            machine_value: row.value.clone(),
+            machine_match_type: row.match_type.clone(),
+            reviewer: reviewer.to_owned(),
+            reviewed_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+}
+
+/// The reviewed CSV that sits next to an output CSV: `paper.csv` is
+/// reviewed into `paper.reviewed.csv`.
+#[must_use]
+#[inline]
+pub fn reviewed_path(output_csv: &Path) -> PathBuf {
+    let stem = output_csv
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    output_csv.with_file_name(format!("{stem}.reviewed.csv"))
+}
+
+/// Reads earlier decisions so an interrupted review can be resumed. A
+/// missing file means nothing has been reviewed yet.
+///
+/// # Errors
+///
+/// Fails when the file exists but cannot be read or a row does not parse.
+#[inline]
+pub fn read_reviewed(
+    path: &Path,
+) -> Result<BTreeMap<String, ReviewedRow>, String> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let rows: Vec<ReviewedRow> = Reader::from_path(path)
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))?
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.field_name.clone(), row))
+        .collect())
+}
+
+/// Writes the decisions made so far, in the order of the output CSV.
+///
+/// # Errors
+///
+/// Fails when the file cannot be created or written.
+#[inline]
+pub fn write_reviewed(
+    path: &Path,
+    rows: &[OutputRow],
+    decisions: &BTreeMap<String, ReviewedRow>,
+) -> Result<(), String> {
+    let mut writer = Writer::from_path(path)
+        .map_err(|e| format!("Failed to create {}: {e}", path.display()))?;
+    for row in rows {
+        if let Some(decision) = decisions.get(&row.field_name) {
+            writer
+                .serialize(decision)
+                .map_err(|e| format!("Failed to write decision: {e}"))?;
+        }
+    }
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Looks up the text under each located value in the paper.
+fn collect_evidence(
+    pdf: &Path,
+    rows: &[OutputRow],
+) -> Result<Vec<String>, String> {
+    let data = fs::read(pdf)
+        .map_err(|e| format!("Failed to read {}: {e}", pdf.display()))?;
+    let document = load_pdf(&data)?;
+    let mut runs_by_page = BTreeMap::new();
+
+    Ok(rows
+        .iter()
+        .map(|row| {
+            let (Some(page), Some(bbox)) = (row.page_number(), row.bbox())
+            else {
+                return String::new();
+            };
+            let runs = runs_by_page.entry(page).or_insert_with(|| {
+                page_runs(&document, page).unwrap_or_default()
+            });
+            evidence_text(runs, &bbox)
+        })
+        .collect())
+}
+
+struct Review {
+    rows: Vec<OutputRow>,
+    evidence: Vec<String>,
+    decisions: BTreeMap<String, ReviewedRow>,
+    reviewer: String,
+    reviewed_path: PathBuf,
+    current: usize,
+    editing: Option<String>,
+}
+
+/// Opens the terminal review of an output CSV. Decisions are written to the
+/// reviewed CSV after every key press that makes one; the output CSV itself
+/// is never modified.
+///
+/// # Errors
+///
+/// Fails when the reviewed CSV would replace the output CSV, a file cannot
+/// be read or written, or the terminal cannot be drawn on or read from.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "the binary calls it from its `review` subcommand"
+)]
+#[inline]
+pub fn run_review(
+    output_csv: &Path,
+    pdf: Option<&Path>,
+    reviewer: &str,
+    reviewed_csv: Option<&Path>,
+) -> Result<(), String> {
+    let reviewed_path = reviewed_csv
+        .map_or_else(|| reviewed_path(output_csv), Path::to_path_buf);
+    if reviewed_path == output_csv {
+        return Err(
+            "The reviewed CSV must not overwrite the machine output".to_owned()
+        );
+    }
+
+    let rows = read_output(output_csv)?;
+    let default_pdf = output_csv.with_extension("pdf");
+    let evidence_pdf =
+        pdf.or_else(|| default_pdf.exists().then_some(&default_pdf));
+    let evidence = match evidence_pdf {
+        Some(path) => collect_evidence(path, &rows)?,
+        None => vec![String::new(); rows.len()],
+    };
+    let decisions = read_reviewed(&reviewed_path)?;
+
+    let mut review = Review {
+        current: rows
+            .iter()
+            .position(|row| !decisions.contains_key(&row.field_name))
+            .unwrap_or_default(),
+        rows,
+        evidence,
+        decisions,
+        reviewer: reviewer.to_owned(),
+        reviewed_path,
+        editing: None,
+    };
+
+    let mut terminal = ratatui::init();
+    let result = review.run(&mut terminal);
+    ratatui::restore();
+    result?;
+
+    println!(
+        "Reviewed {} of {} fields; decisions written to {}",
+        review.decisions.len(),
+        review.rows.len(),
+        review.reviewed_path.display()
+    );
+    Ok(())
+}
+
+impl Review {
+    #[expect(
+        clippy::wildcard_enum_match_arm,
+        reason = "keys without a shortcut are ignored"
+    )]
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), String> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .map_err(|e| format!("Failed to draw: {e}"))?;
+            let Event::Key(key) = event::read()
+                .map_err(|e| format!("Failed to read key: {e}"))?
+            else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if self.editing.is_some() {
+                self.edit_key(key.code)?;
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('a') | KeyCode::Enter => {
+                    let value = self.rows[self.current].value.clone();
+                    self.decide(Decision::Accepted, value)?;
+                }
+                KeyCode::Char('e') => {
+                    self.editing = Some(self.current_value());
+                }
+                KeyCode::Char('n') => {
+                    self.decide(Decision::NotFound, String::new())?;
+                }
+                KeyCode::Right | KeyCode::Down | KeyCode::Char('j') => {
+                    self.move_by(1);
+                }
+                KeyCode::Left | KeyCode::Up | KeyCode::Char('k') => {
+                    self.current = self.current.saturating_sub(1);
+                }
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    #[expect(
+        clippy::wildcard_enum_match_arm,
+        reason = "keys without a shortcut are ignored"
+    )]
+    fn edit_key(&mut self, code: KeyCode) -> Result<(), String> {
+        let Some(input) = self.editing.as_mut() else {
+            return Ok(());
+        };
+        match code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.editing = None,
+            KeyCode::Enter => {
+                let value = self.editing.take().unwrap_or_default();
+                self.decide(Decision::Edited, value)?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The value shown for the current field: the reviewed one if a
+    /// decision exists, the machine's otherwise.
+    fn current_value(&self) -> String {
+        let row = &self.rows[self.current];
+        self.decisions.get(&row.field_name).map_or_else(
+            || row.value.clone(),
+            |decision| decision.value.clone(),
+        )
+    }
+
+    fn move_by(&mut self, step: usize) {
+        self.current = self
+            .current
+            .saturating_add(step)
+            .min(self.rows.len().saturating_sub(1));
+    }
+
+    fn decide(
+        &mut self,
+        decision: Decision,
+        value: String,
+    ) -> Result<(), String> {
+        let row = &self.rows[self.current];
+        let reviewed = ReviewedRow::new(row, decision, value, &self.reviewer);
+        self.decisions.insert(row.field_name.clone(), reviewed);
+        write_reviewed(&self.reviewed_path, &self.rows, &self.decisions)?;
+        self.move_by(1);
+        Ok(())
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let row = &self.rows[self.current];
+        let [header, body, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+
+        frame.render_widget(
+            Line::styled(
+                format!(
+                    "Field {}/{}: {}   ({} reviewed)",
+                    self.current.saturating_add(1),
+                    self.rows.len(),
+                    row.field_name,
+                    self.decisions.len()
+                ),
+                Style::new().add_modifier(Modifier::BOLD),
+            ),
+            header,
+        );
+
+        let mut lines = vec![
+            Line::from(format!("Value:      {}", row.value)),
+            Line::from(format!("Match type: {}", row.match_type)),
+            Line::from(format!("Comment:    {}", row.comment)),
+            Line::from(format!("Location:   {}", location(row))),
+        ];
+        if !row.candidates.is_empty() {
+            lines.push(Line::from("Candidates:"));
+            lines.extend(candidate_lines(row).into_iter().map(Line::from));
+        }
+        if let Some(reviewed) = self.decisions.get(&row.field_name) {
+            lines.push(Line::from(format!(
+                "Decision:   {:?} by {} at {}: {}",
+                reviewed.decision,
+                reviewed.reviewer,
+                reviewed.reviewed_at,
+                reviewed.value
+            )));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from("Evidence:"));
+        lines.extend(self.evidence[self.current].lines().map(Line::from));
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(Block::new().borders(Borders::TOP))
+                .wrap(Wrap { trim: false }),
+            body,
+        );
+
+        let footer_text = self.editing.as_ref().map_or_else(
+            || HELP.to_owned(),
+            |input| format!("New value: {input}\u{2588}\n{EDIT_HELP}"),
+        );
+        frame.render_widget(
+            Paragraph::new(footer_text)
+                .block(Block::new().borders(Borders::TOP)),
+            footer,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDir;
+
+    fn output_row() -> OutputRow {
+        OutputRow {
+            field_name: "mean_age".to_owned(),
+            value: "45".to_owned(),
+            match_type: "found".to_owned(),
+            comment: String::new(),
+            page: "3".to_owned(),
+            xmin: "10".to_owned(),
+            ymin: "20".to_owned(),
+            xmax: "30".to_owned(),
+            ymax: "40".to_owned(),
+            candidates: String::new(),
+        }
+    }
+
+    #[test]
+    fn reviewed_csv_sits_next_to_output() {
+        assert_eq!(
+            reviewed_path(Path::new("papers/smith.csv")),
+            Path::new("papers/smith.reviewed.csv")
+        );
+    }
+
+    #[test]
+    fn not_found_clears_value_and_location() {
+        let reviewed = ReviewedRow::new(
+            &output_row(),
+            Decision::NotFound,
+            "ignored".to_owned(),
+            "ab",
+        );
+        assert_eq!(reviewed.value, "");
+        assert_eq!(reviewed.match_type, "not_found");
+        assert_eq!(reviewed.page, "");
+        assert_eq!(reviewed.machine_value, "45");
+    }
+
+    #[test]
+    fn edits_to_missing_values_count_as_found() {
+        let mut row = output_row();
+        row.value = "NULL".to_owned();
+        row.match_type = "not_found".to_owned();
+        let reviewed =
+            ReviewedRow::new(&row, Decision::Edited, "52".to_owned(), "ab");
+        assert_eq!(reviewed.value, "52");
+        assert_eq!(reviewed.match_type, "found");
+        assert_eq!(reviewed.machine_match_type, "not_found");
+    }
+
+    #[test]
+    fn decisions_round_trip() {
+        let dir = TestDir::new("review-round-trip");
+        let path = dir.join("reviewed.csv");
+        let row = output_row();
+        let reviewed =
+            ReviewedRow::new(&row, Decision::Edited, "46".to_owned(), "ab");
+        let decisions =
+            BTreeMap::from([(row.field_name.clone(), reviewed.clone())]);
+
+        write_reviewed(&path, &[row], &decisions).unwrap();
+        let read_back = read_reviewed(&path).unwrap();
+
+        assert_eq!(read_back.get("mean_age"), Some(&reviewed));
+    }
+}
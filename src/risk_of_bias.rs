@@ -0,0 +1,837 @@
+use crate::Extraction;
+use crate::extraction::value_text;
+use crate::schema::Schema;
+use clap::ValueEnum;
+use csv::Writer;
+
+const ROB2_TEMPLATE: &str = include_str!("rob2.csv");
+const ROBINS_I_TEMPLATE: &str = include_str!("robins_i.csv");
+const NEWCASTLE_OTTAWA_TEMPLATE: &str = include_str!("newcastle_ottawa.csv");
+
+const SIGNALLING_ANSWERS: &str = "Answer each signalling question with Y (yes), PY (probably yes), PN (probably no), N (no) or NI (no information), and with NA when the condition at the start of a question does not hold.";
+
+/// A risk-of-bias tool with a built-in schema of its questions.
+///
+/// The model only answers the questions; the domain and overall judgements
+/// are derived from the answers by the tool's own algorithm.
+///
+/// The `RoB 2` overall judgement is the worst domain judgement. The tool also
+/// lets a reviewer rate a trial high when several domains have some concerns;
+/// that call is left to the reviewer.
+///
+/// The ROBINS-I template is simplified. It leaves out questions 1.2 and 1.3
+/// on time-varying confounding, 1.7 and 1.8 on adjusting for it, and 4.3 to
+/// 4.6 on adherence and co-interventions, so it suits the effect of
+/// assignment with baseline confounding. Only confounding can be judged
+/// critical; the other domains stop at serious.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[non_exhaustive]
+pub enum BiasTool {
+    /// Cochrane risk-of-bias tool version 2, for randomised trials.
+    Rob2,
+    /// ROBINS-I, for non-randomised studies of interventions.
+    RobinsI,
+    /// Newcastle-Ottawa Scale, for cohort studies.
+    NewcastleOttawa,
+}
+
+impl BiasTool {
+    #[must_use]
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Rob2 => "rob2",
+            Self::RobinsI => "robins-i",
+            Self::NewcastleOttawa => "newcastle-ottawa",
+        }
+    }
+
+    const fn template(self) -> &'static str {
+        match self {
+            Self::Rob2 => ROB2_TEMPLATE,
+            Self::RobinsI => ROBINS_I_TEMPLATE,
+            Self::NewcastleOttawa => NEWCASTLE_OTTAWA_TEMPLATE,
+        }
+    }
+
+    /// The tool's questions as categorical fields, batched by domain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the built-in template does not parse, which the tests rule
+    /// out.
+    #[must_use]
+    #[inline]
+    pub fn schema(self) -> Schema {
+        Schema::parse(self.template()).unwrap_or_else(|e| {
+            panic!("Built-in {} template is invalid: {e}", self.name())
+        })
+    }
+
+    /// Domain context explaining the tool to the model, used when the run
+    /// has none of its own.
+    #[must_use]
+    #[inline]
+    pub fn context(self) -> String {
+        match self {
+            Self::Rob2 => format!(
+                "These are the signalling questions of the Cochrane RoB 2 tool for the effect of assignment to intervention in a randomised trial. Answer them for the trial's primary outcome. {SIGNALLING_ANSWERS} Do not rate the risk of bias yourself."
+            ),
+            Self::RobinsI => format!(
+                "These are the signalling questions of the ROBINS-I tool for a non-randomised study of an intervention. Answer them for the study's primary outcome. {SIGNALLING_ANSWERS} Do not rate the risk of bias yourself."
+            ),
+            Self::NewcastleOttawa => "These are the items of the Newcastle-Ottawa Scale for cohort studies. Answer each with the option that describes the study. Do not score the study yourself.".to_owned(),
+        }
+    }
+
+    /// The domains judged, in the order of [`Assessment::domains`].
+    #[must_use]
+    #[inline]
+    pub const fn domains(self) -> &'static [&'static str] {
+        match self {
+            Self::Rob2 => &[
+                "randomization",
+                "deviations",
+                "missing_data",
+                "measurement",
+                "reporting",
+            ],
+            Self::RobinsI => &[
+                "confounding",
+                "selection",
+                "classification",
+                "deviations",
+                "missing_data",
+                "measurement",
+                "reporting",
+            ],
+            Self::NewcastleOttawa => &["selection", "comparability", "outcome"],
+        }
+    }
+
+    /// Judges one paper from the answers in its extraction. Only found or
+    /// inferred answers count; any other question counts as no information.
+    #[must_use]
+    #[inline]
+    pub fn assess(self, paper: &str, extraction: &Extraction) -> Assessment {
+        let answers = Answers(extraction);
+        let (judgements, overall) = match self {
+            Self::Rob2 => rob2(&answers),
+            Self::RobinsI => robins_i(&answers),
+            Self::NewcastleOttawa => newcastle_ottawa(&answers),
+        };
+        Assessment {
+            paper: paper.to_owned(),
+            overall,
+            domains: self
+                .domains()
+                .iter()
+                .zip(judgements)
+                .map(|(&domain, judgement)| DomainJudgement {
+                    domain,
+                    judgement,
+                })
+                .collect(),
+            answers: self
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| {
+                    answers.text(&field.field_name).unwrap_or_default()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A domain's judgement for one paper: a risk-of-bias level, or the stars
+/// awarded for the Newcastle-Ottawa Scale.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DomainJudgement {
+    pub domain: &'static str,
+    pub judgement: String,
+}
+
+/// A paper's risk-of-bias judgements and the answers they were derived from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Assessment {
+    pub paper: String,
+    pub overall: String,
+    pub domains: Vec<DomainJudgement>,
+    /// Each question's answer in schema order, empty when the model found
+    /// none.
+    pub answers: Vec<String>,
+}
+
+/// Writes one row per paper: the overall judgement, each domain's judgement
+/// and each question's answer.
+///
+/// # Errors
+///
+/// Fails when the file cannot be written.
+#[inline]
+pub fn write_assessment_csv(
+    path: &str,
+    tool: BiasTool,
+    assessments: &[Assessment],
+) -> Result<(), String> {
+    let write_error = |e: csv::Error| format!("Failed to write {path}: {e}");
+    let mut writer = Writer::from_path(path).map_err(write_error)?;
+
+    let mut headers = vec!["paper".to_owned(), "overall".to_owned()];
+    headers.extend(tool.domains().iter().map(|&domain| domain.to_owned()));
+    headers.extend(
+        tool.schema()
+            .fields()
+            .iter()
+            .map(|field| field.field_name.clone()),
+    );
+    writer.write_record(&headers).map_err(write_error)?;
+
+    for assessment in assessments {
+        let mut row =
+            vec![assessment.paper.clone(), assessment.overall.clone()];
+        row.extend(
+            assessment
+                .domains
+                .iter()
+                .map(|domain| domain.judgement.clone()),
+        );
+        row.extend(assessment.answers.iter().cloned());
+        writer.write_record(&row).map_err(write_error)?;
+    }
+
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+/// The answers of one paper, read from its extraction.
+struct Answers<'a>(&'a Extraction);
+
+impl Answers<'_> {
+    /// A found or inferred answer.
+    fn text(&self, question: &str) -> Option<String> {
+        let field = self.0.get(question)?;
+        if !matches!(field.match_type.as_str(), "found" | "inferred") {
+            return None;
+        }
+        field
+            .value
+            .as_ref()
+            .map(|value| value_text(value).trim().to_owned())
+    }
+
+    fn get(&self, question: &str) -> Answer {
+        self.text(question)
+            .map_or(Answer::NoInformation, |text| Answer::from_text(&text))
+    }
+
+    /// A Newcastle-Ottawa option, normalised like the template's options.
+    fn option(&self, item: &str) -> String {
+        self.text(item)
+            .unwrap_or_default()
+            .to_lowercase()
+            .replace([' ', '-'], "_")
+    }
+}
+
+/// An answer to a signalling question.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+    Yes,
+    ProbablyYes,
+    ProbablyNo,
+    No,
+    /// No information, and also not applicable: the algorithms only reach
+    /// a conditional question when its condition holds.
+    NoInformation,
+}
+
+impl Answer {
+    fn from_text(text: &str) -> Self {
+        match text.to_uppercase().as_str() {
+            "Y" | "YES" => Self::Yes,
+            "PY" | "PROBABLY YES" => Self::ProbablyYes,
+            "PN" | "PROBABLY NO" => Self::ProbablyNo,
+            "N" | "NO" => Self::No,
+            _ => Self::NoInformation,
+        }
+    }
+
+    const fn is_yes(self) -> bool {
+        matches!(self, Self::Yes | Self::ProbablyYes)
+    }
+
+    const fn is_no(self) -> bool {
+        matches!(self, Self::No | Self::ProbablyNo)
+    }
+}
+
+/// `RoB 2` judgements, from least to most concerning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Rob2Judgement {
+    Low,
+    SomeConcerns,
+    High,
+}
+
+impl Rob2Judgement {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Low => "Low",
+            Self::SomeConcerns => "Some concerns",
+            Self::High => "High",
+        }
+    }
+}
+
+/// The `RoB 2` domain judgements and the overall judgement, which is the
+/// worst of them.
+fn rob2(answers: &Answers<'_>) -> (Vec<String>, String) {
+    let judgements = [
+        rob2_randomization(answers),
+        rob2_deviations(answers),
+        rob2_missing_data(answers),
+        rob2_measurement(answers),
+        rob2_reporting(answers),
+    ];
+    let overall = judgements
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(Rob2Judgement::Low);
+    (
+        judgements
+            .iter()
+            .map(|judgement| judgement.name().to_owned())
+            .collect(),
+        overall.name().to_owned(),
+    )
+}
+
+fn rob2_randomization(answers: &Answers<'_>) -> Rob2Judgement {
+    let random = answers.get("q1_1");
+    let concealed = answers.get("q1_2");
+    let imbalance = answers.get("q1_3");
+    if concealed.is_no() {
+        Rob2Judgement::High
+    } else if concealed.is_yes() {
+        if imbalance.is_yes() || random.is_no() {
+            Rob2Judgement::SomeConcerns
+        } else {
+            Rob2Judgement::Low
+        }
+    } else if imbalance.is_yes() {
+        Rob2Judgement::High
+    } else {
+        Rob2Judgement::SomeConcerns
+    }
+}
+
+/// Deviations from the intended interventions, judged on adherence and on
+/// the analysis, whichever is worse.
+fn rob2_deviations(answers: &Answers<'_>) -> Rob2Judgement {
+    let blinded = answers.get("q2_1").is_no() && answers.get("q2_2").is_no();
+    let deviated = answers.get("q2_3");
+    let adherence = if blinded || deviated.is_no() {
+        Rob2Judgement::Low
+    } else if !deviated.is_yes()
+        || answers.get("q2_4").is_no()
+        || answers.get("q2_5").is_yes()
+    {
+        Rob2Judgement::SomeConcerns
+    } else {
+        Rob2Judgement::High
+    };
+    let analysis = if answers.get("q2_6").is_yes() {
+        Rob2Judgement::Low
+    } else if answers.get("q2_7").is_no() {
+        Rob2Judgement::SomeConcerns
+    } else {
+        Rob2Judgement::High
+    };
+    adherence.max(analysis)
+}
+
+fn rob2_missing_data(answers: &Answers<'_>) -> Rob2Judgement {
+    if answers.get("q3_1").is_yes()
+        || answers.get("q3_2").is_yes()
+        || answers.get("q3_3").is_no()
+    {
+        Rob2Judgement::Low
+    } else if answers.get("q3_4").is_no() {
+        Rob2Judgement::SomeConcerns
+    } else {
+        Rob2Judgement::High
+    }
+}
+
+/// Measurement of the outcome. When it is unknown whether measurement
+/// differed between groups, the domain is at best of some concern.
+fn rob2_measurement(answers: &Answers<'_>) -> Rob2Judgement {
+    let differed = answers.get("q4_2");
+    if answers.get("q4_1").is_yes() || differed.is_yes() {
+        return Rob2Judgement::High;
+    }
+    let assessors =
+        if answers.get("q4_3").is_no() || answers.get("q4_4").is_no() {
+            Rob2Judgement::Low
+        } else if answers.get("q4_5").is_no() {
+            Rob2Judgement::SomeConcerns
+        } else {
+            Rob2Judgement::High
+        };
+    if differed.is_no() {
+        assessors
+    } else {
+        assessors.max(Rob2Judgement::SomeConcerns)
+    }
+}
+
+fn rob2_reporting(answers: &Answers<'_>) -> Rob2Judgement {
+    let measurements = answers.get("q5_2");
+    let analyses = answers.get("q5_3");
+    if measurements.is_yes() || analyses.is_yes() {
+        Rob2Judgement::High
+    } else if measurements.is_no()
+        && analyses.is_no()
+        && answers.get("q5_1").is_yes()
+    {
+        Rob2Judgement::Low
+    } else {
+        Rob2Judgement::SomeConcerns
+    }
+}
+
+/// ROBINS-I judgements. A domain without enough information ranks above
+/// moderate, so the overall judgement is no information only when no
+/// domain is serious or critical.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum RobinsJudgement {
+    Low,
+    Moderate,
+    NoInformation,
+    Serious,
+    Critical,
+}
+
+impl RobinsJudgement {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Low => "Low",
+            Self::Moderate => "Moderate",
+            Self::NoInformation => "No information",
+            Self::Serious => "Serious",
+            Self::Critical => "Critical",
+        }
+    }
+
+    /// Moderate when the answer is yes, serious when it is no.
+    const fn from_mitigation(answer: Answer) -> Self {
+        if answer.is_yes() {
+            Self::Moderate
+        } else if answer.is_no() {
+            Self::Serious
+        } else {
+            Self::NoInformation
+        }
+    }
+}
+
+/// The ROBINS-I domain judgements and the overall judgement, which is the
+/// worst of them.
+fn robins_i(answers: &Answers<'_>) -> (Vec<String>, String) {
+    let judgements = [
+        robins_confounding(answers),
+        robins_selection(answers),
+        robins_classification(answers),
+        robins_deviations(answers),
+        robins_missing_data(answers),
+        robins_measurement(answers),
+        robins_reporting(answers),
+    ];
+    let overall = judgements
+        .iter()
+        .copied()
+        .max()
+        .unwrap_or(RobinsJudgement::Low);
+    (
+        judgements
+            .iter()
+            .map(|judgement| judgement.name().to_owned())
+            .collect(),
+        overall.name().to_owned(),
+    )
+}
+
+/// Confounding. An analysis that clearly did not control for confounding is
+/// critical; one that probably did not is serious.
+fn robins_confounding(answers: &Answers<'_>) -> RobinsJudgement {
+    let potential = answers.get("q1_1");
+    if potential.is_no() {
+        return RobinsJudgement::Low;
+    }
+    if !potential.is_yes() {
+        return RobinsJudgement::NoInformation;
+    }
+    let method = answers.get("q1_4");
+    let controlled = if method.is_yes() {
+        RobinsJudgement::from_mitigation(answers.get("q1_5"))
+    } else if method == Answer::No {
+        RobinsJudgement::Critical
+    } else if method == Answer::ProbablyNo {
+        RobinsJudgement::Serious
+    } else {
+        RobinsJudgement::NoInformation
+    };
+    if answers.get("q1_6").is_yes() {
+        controlled.max(RobinsJudgement::Serious)
+    } else {
+        controlled
+    }
+}
+
+fn robins_selection(answers: &Answers<'_>) -> RobinsJudgement {
+    let after_start = answers.get("q2_1");
+    let associated = answers.get("q2_2");
+    let influenced = answers.get("q2_3");
+    let coincide = answers.get("q2_4");
+    if (after_start.is_yes() && associated.is_yes() && influenced.is_yes())
+        || coincide.is_no()
+    {
+        RobinsJudgement::from_mitigation(answers.get("q2_5"))
+    } else if coincide.is_yes()
+        && (after_start.is_no()
+            || (after_start.is_yes()
+                && (associated.is_no() || influenced.is_no())))
+    {
+        RobinsJudgement::Low
+    } else {
+        RobinsJudgement::NoInformation
+    }
+}
+
+fn robins_classification(answers: &Answers<'_>) -> RobinsJudgement {
+    let defined = answers.get("q3_1");
+    let affected = answers.get("q3_3");
+    if defined.is_no() || affected.is_yes() {
+        RobinsJudgement::Serious
+    } else if defined.is_yes() && affected.is_no() {
+        let recorded = answers.get("q3_2");
+        if recorded.is_yes() {
+            RobinsJudgement::Low
+        } else if recorded.is_no() {
+            RobinsJudgement::Moderate
+        } else {
+            RobinsJudgement::NoInformation
+        }
+    } else {
+        RobinsJudgement::NoInformation
+    }
+}
+
+fn robins_deviations(answers: &Answers<'_>) -> RobinsJudgement {
+    let deviated = answers.get("q4_1");
+    if deviated.is_no() {
+        return RobinsJudgement::Low;
+    }
+    let unbalanced = answers.get("q4_2");
+    if !deviated.is_yes() {
+        RobinsJudgement::NoInformation
+    } else if unbalanced.is_no() {
+        RobinsJudgement::Moderate
+    } else if unbalanced.is_yes() {
+        RobinsJudgement::Serious
+    } else {
+        RobinsJudgement::NoInformation
+    }
+}
+
+/// Missing data. Incomplete data is moderate when the missingness is
+/// balanced or the results are robust to it, and serious when neither.
+fn robins_missing_data(answers: &Answers<'_>) -> RobinsJudgement {
+    let available = answers.get("q5_1");
+    let intervention_missing = answers.get("q5_2");
+    let other_missing = answers.get("q5_3");
+    if available.is_yes()
+        && intervention_missing.is_no()
+        && other_missing.is_no()
+    {
+        return RobinsJudgement::Low;
+    }
+    if !(available.is_no()
+        || intervention_missing.is_yes()
+        || other_missing.is_yes())
+    {
+        return RobinsJudgement::NoInformation;
+    }
+    let balanced = answers.get("q5_4");
+    let robust = answers.get("q5_5");
+    if balanced.is_yes() || robust.is_yes() {
+        RobinsJudgement::Moderate
+    } else if balanced.is_no() && robust.is_no() {
+        RobinsJudgement::Serious
+    } else {
+        RobinsJudgement::NoInformation
+    }
+}
+
+/// Measurement of outcomes. Unblinded assessment of an outcome that
+/// knowledge of the intervention could influence is serious; when either
+/// is unknown it is moderate.
+fn robins_measurement(answers: &Answers<'_>) -> RobinsJudgement {
+    let influenced = answers.get("q6_1");
+    let aware = answers.get("q6_2");
+    let comparable = answers.get("q6_3");
+    let systematic = answers.get("q6_4");
+    if (influenced.is_yes() && aware.is_yes())
+        || systematic.is_yes()
+        || comparable.is_no()
+    {
+        RobinsJudgement::Serious
+    } else if !(comparable.is_yes() && systematic.is_no()) {
+        RobinsJudgement::NoInformation
+    } else if influenced.is_no() || aware.is_no() {
+        RobinsJudgement::Low
+    } else {
+        RobinsJudgement::Moderate
+    }
+}
+
+fn robins_reporting(answers: &Answers<'_>) -> RobinsJudgement {
+    let selected =
+        ["q7_1", "q7_2", "q7_3"].map(|question| answers.get(question));
+    if selected.iter().any(|answer| answer.is_yes()) {
+        RobinsJudgement::Serious
+    } else if selected.iter().all(|answer| answer.is_no()) {
+        RobinsJudgement::Low
+    } else {
+        RobinsJudgement::NoInformation
+    }
+}
+
+/// The stars awarded in each Newcastle-Ottawa category and the quality
+/// they amount to under the AHRQ thresholds.
+fn newcastle_ottawa(answers: &Answers<'_>) -> (Vec<String>, String) {
+    let starred = |item: &str, options: &[&str]| {
+        options.contains(&answers.option(item).as_str())
+    };
+    let selection = [
+        starred(
+            "representativeness",
+            &["truly_representative", "somewhat_representative"],
+        ),
+        starred("non_exposed_selection", &["same_community"]),
+        starred(
+            "exposure_ascertainment",
+            &["secure_record", "structured_interview"],
+        ),
+        starred("outcome_absent_at_start", &["yes"]),
+    ]
+    .into_iter()
+    .filter(|&star| star)
+    .count();
+    let comparability: usize = match answers.option("comparability").as_str() {
+        "most_important_and_additional" => 2,
+        "most_important_only" | "additional_only" => 1,
+        _ => 0,
+    };
+    let outcome = [
+        starred(
+            "outcome_assessment",
+            &["independent_blind", "record_linkage"],
+        ),
+        starred("follow_up_long_enough", &["yes"]),
+        starred(
+            "follow_up_adequacy",
+            &["complete", "small_loss_unlikely_bias"],
+        ),
+    ]
+    .into_iter()
+    .filter(|&star| star)
+    .count();
+
+    let quality = if comparability == 0 || outcome < 2 || selection < 2 {
+        "Poor"
+    } else if selection == 2 {
+        "Fair"
+    } else {
+        "Good"
+    };
+    (
+        vec![
+            format!("{selection}/4"),
+            format!("{comparability}/2"),
+            format!("{outcome}/3"),
+        ],
+        quality.to_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::ExtractedField;
+    use crate::schema::SchemaKind;
+    use serde_json::Value;
+    use std::collections::HashSet;
+
+    fn extraction(answers: &[(&str, &str)]) -> Extraction {
+        answers
+            .iter()
+            .map(|&(question, answer)| {
+                (
+                    question.to_owned(),
+                    ExtractedField {
+                        value: Some(Value::from(answer)),
+                        match_type: "found".to_owned(),
+                        comment: None,
+                        page: None,
+                        bbox: None,
+                        warnings: Vec::new(),
+                        candidates: Vec::new(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn judgements(assessment: &Assessment) -> Vec<&str> {
+        assessment
+            .domains
+            .iter()
+            .map(|domain| domain.judgement.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn templates_batch_categorical_questions_by_domain() {
+        for tool in
+            [BiasTool::Rob2, BiasTool::RobinsI, BiasTool::NewcastleOttawa]
+        {
+            let schema = tool.schema();
+            assert!(schema.fields().iter().all(|field| {
+                matches!(field.kind, SchemaKind::Categorical)
+                    && field.infer
+                    && !field.options.is_empty()
+            }));
+            let batches: HashSet<_> = schema
+                .fields()
+                .iter()
+                .filter_map(|field| field.batch.as_deref())
+                .collect();
+            assert_eq!(batches.len(), tool.domains().len(), "{}", tool.name());
+        }
+    }
+
+    #[test]
+    fn rob2_takes_the_worst_domain() {
+        let low = [
+            ("q1_1", "Y"),
+            ("q1_2", "Y"),
+            ("q1_3", "N"),
+            ("q2_1", "N"),
+            ("q2_2", "PN"),
+            ("q2_6", "Y"),
+            ("q3_1", "Y"),
+            ("q4_1", "N"),
+            ("q4_2", "N"),
+            ("q4_3", "N"),
+            ("q5_1", "Y"),
+            ("q5_2", "N"),
+            ("q5_3", "N"),
+        ];
+        let trial = BiasTool::Rob2.assess("a.pdf", &extraction(&low));
+        assert_eq!(trial.overall, "Low");
+        assert_eq!(trial.answers[0], "Y");
+        assert_eq!(trial.answers[3], "N");
+        assert_eq!(trial.answers[5], "");
+
+        let mut unconcealed = low.to_vec();
+        unconcealed[1] = ("q1_2", "PN");
+        unconcealed[10] = ("q5_1", "NI");
+        let flawed = BiasTool::Rob2.assess("b.pdf", &extraction(&unconcealed));
+        assert_eq!(
+            judgements(&flawed),
+            ["High", "Low", "Low", "Low", "Some concerns"]
+        );
+        assert_eq!(flawed.overall, "High");
+
+        let unanswered = BiasTool::Rob2.assess("c.pdf", &extraction(&[]));
+        assert_eq!(
+            judgements(&unanswered),
+            ["Some concerns", "High", "High", "High", "Some concerns"]
+        );
+    }
+
+    #[test]
+    fn robins_i_ranks_no_information_below_serious() {
+        let answers = [
+            ("q1_1", "Y"),
+            ("q1_4", "Y"),
+            ("q1_5", "PY"),
+            ("q1_6", "N"),
+            ("q2_1", "N"),
+            ("q2_4", "Y"),
+            ("q3_1", "Y"),
+            ("q3_2", "Y"),
+            ("q3_3", "N"),
+            ("q4_1", "N"),
+            ("q5_1", "Y"),
+            ("q5_2", "N"),
+            ("q5_3", "N"),
+            ("q6_1", "Y"),
+            ("q6_2", "N"),
+            ("q6_3", "Y"),
+            ("q6_4", "N"),
+            ("q7_1", "N"),
+            ("q7_2", "N"),
+            ("q7_3", "N"),
+        ];
+        let study = BiasTool::RobinsI.assess("a.pdf", &extraction(&answers));
+        assert_eq!(
+            judgements(&study),
+            ["Moderate", "Low", "Low", "Low", "Low", "Low", "Low"]
+        );
+        assert_eq!(study.overall, "Moderate");
+
+        let mut unreported = answers.to_vec();
+        unreported[17] = ("q7_1", "NI");
+        let unclear =
+            BiasTool::RobinsI.assess("b.pdf", &extraction(&unreported));
+        assert_eq!(unclear.overall, "No information");
+
+        unreported[3] = ("q1_6", "PY");
+        let adjusted =
+            BiasTool::RobinsI.assess("c.pdf", &extraction(&unreported));
+        assert_eq!(adjusted.domains[0].judgement, "Serious");
+        assert_eq!(adjusted.overall, "Serious");
+    }
+
+    #[test]
+    fn newcastle_ottawa_counts_stars() {
+        let answers = [
+            ("representativeness", "somewhat_representative"),
+            ("non_exposed_selection", "same_community"),
+            ("exposure_ascertainment", "written_self_report"),
+            ("outcome_absent_at_start", "yes"),
+            ("comparability", "most_important_only"),
+            ("outcome_assessment", "record linkage"),
+            ("follow_up_long_enough", "yes"),
+            ("follow_up_adequacy", "large_loss_no_description"),
+        ];
+        let cohort =
+            BiasTool::NewcastleOttawa.assess("a.pdf", &extraction(&answers));
+        assert_eq!(judgements(&cohort), ["3/4", "1/2", "2/3"]);
+        assert_eq!(cohort.overall, "Good");
+
+        let mut unadjusted = answers.to_vec();
+        unadjusted[4] = ("comparability", "none");
+        let poor =
+            BiasTool::NewcastleOttawa.assess("b.pdf", &extraction(&unadjusted));
+        assert_eq!(poor.overall, "Poor");
+    }
+}
@@ -1,26 +1,170 @@
+use core::fmt;
 use csv::Reader;
 use serde::Deserialize;
 use serde::de::Error as DeError;
 use serde_json::{Value, json};
 use std::collections::HashSet;
 use std::fs;
+use std::path::Path;
 
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum SchemaKind {
     Categorical,
     Number,
     Text,
 }
 
+impl SchemaKind {
+    /// The name used for this kind in the schema CSV.
+    #[must_use]
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        match *self {
+            Self::Categorical => "categorical",
+            Self::Number => "number",
+            Self::Text => "text",
+        }
+    }
+}
+
+/// Every `match_type` a field's answer may have. The prompt describes each
+/// of them with the same spelling.
+pub const MATCH_TYPES: [&str; 5] = [
+    "found",
+    "not_found",
+    "inferred",
+    "not_applicable",
+    "ambiguous",
+];
+
+/// Longest field description accepted unless another limit is given, in
+/// characters.
+pub const DEFAULT_MAX_DESCRIPTION_CHARS: usize = 500;
+
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct SchemaField {
     pub field_name: String,
     pub description: String,
     pub kind: SchemaKind,
     pub infer: bool,
+    /// Fields with the same batch key are always sent in the same request.
+    pub batch: Option<String>,
+    /// Longer instructions for the model, shown under the description.
+    pub guidance: Option<String>,
+    /// Only extract this field when another field's answer meets the
+    /// condition; otherwise it is `not_applicable`.
+    pub when: Option<Condition>,
+    /// Ask for up to this many values the paper reports for the field, each
+    /// with its location, instead of only for `ambiguous` answers.
+    pub max_candidates: Option<usize>,
+    /// The answers allowed for a categorical field, from the `options`
+    /// column with answers separated by `|`. Empty allows any text.
+    pub options: Vec<String>,
+}
+
+#[cfg(test)]
+impl SchemaField {
+    /// A field with a name and kind and nothing else, for tests to adjust
+    /// with struct update syntax.
+    pub(crate) fn new(name: &str, kind: SchemaKind) -> Self {
+        Self {
+            field_name: name.to_owned(),
+            description: String::new(),
+            kind,
+            infer: false,
+            batch: None,
+            guidance: None,
+            when: None,
+            max_candidates: None,
+            options: Vec::new(),
+        }
+    }
+}
+
+/// How a [`Condition`] compares a field's answer with its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+}
+
+/// A test on another field's answer, from the schema's `when` column.
+///
+/// Written as `FIELD == "VALUE"` or `FIELD != "VALUE"`. Answers are compared
+/// ignoring case and surrounding spaces; a field with no value compares as
+/// an empty string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Condition {
+    pub field: String,
+    pub comparison: Comparison,
+    pub value: String,
+}
+
+impl Condition {
+    /// Parses `FIELD == "VALUE"` or `FIELD != "VALUE"`.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the text has neither comparison or names no field.
+    #[inline]
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (field, comparison, quoted) = if let Some((before, after)) =
+            text.split_once("!=")
+        {
+            (before, Comparison::NotEqual, after)
+        } else if let Some((before, after)) = text.split_once("==") {
+            (before, Comparison::Equal, after)
+        } else {
+            return Err(format!(
+                "Invalid condition '{text}'. Must be FIELD == \"VALUE\" or FIELD != \"VALUE\""
+            ));
+        };
+        let field_name = field.trim();
+        if field_name.is_empty() {
+            return Err(format!("Condition '{text}' names no field"));
+        }
+        let trimmed = quoted.trim();
+        let value = trimmed
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+            .unwrap_or(trimmed);
+        Ok(Self {
+            field: field_name.to_owned(),
+            comparison,
+            value: value.trim().to_owned(),
+        })
+    }
+
+    /// Whether `answer`, the text of the tested field's value, meets the
+    /// condition.
+    #[must_use]
+    #[inline]
+    pub fn holds(&self, answer: &str) -> bool {
+        let equal = answer.trim().to_lowercase() == self.value.to_lowercase();
+        match self.comparison {
+            Comparison::Equal => equal,
+            Comparison::NotEqual => !equal,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self.comparison {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+        };
+        write!(f, "{} {operator} \"{}\"", self.field, self.value)
+    }
 }
 
 impl<'de> Deserialize<'de> for SchemaField {
+    #[inline]
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
@@ -31,6 +175,16 @@ impl<'de> Deserialize<'de> for SchemaField {
             description: String,
             kind: String,
             infer: String,
+            #[serde(default)]
+            batch: String,
+            #[serde(default)]
+            guidance: String,
+            #[serde(default)]
+            when: String,
+            #[serde(default)]
+            candidates: String,
+            #[serde(default)]
+            options: String,
         }
 
         let raw = RawSchemaField::deserialize(deserializer)?;
@@ -51,22 +205,6 @@ impl<'de> Deserialize<'de> for SchemaField {
             )));
         }
 
-        // Validate description
-        if raw.description.len() > 120 {
-            return Err(DeError::custom(format!(
-                "Description for field '{}' exceeds 100 characters (length: {})",
-                raw.field_name,
-                raw.description.len()
-            )));
-        }
-
-        if !raw.description.is_ascii() {
-            return Err(DeError::custom(format!(
-                "Description for field '{}' contains non-ASCII characters",
-                raw.field_name
-            )));
-        }
-
         // Parse kind with error reporting (must be lowercase)
         let kind = match raw.kind.as_str() {
             "categorical" => SchemaKind::Categorical,
@@ -92,16 +230,79 @@ impl<'de> Deserialize<'de> for SchemaField {
             }
         };
 
+        let when = Some(raw.when.trim())
+            .filter(|condition| !condition.is_empty())
+            .map(Condition::parse)
+            .transpose()
+            .map_err(|e| {
+                DeError::custom(format!("Field '{}': {e}", raw.field_name))
+            })?;
+
+        let max_candidates = match raw.candidates.trim() {
+            "" => None,
+            count => match count.parse::<usize>() {
+                Ok(limit) if limit >= 2 => Some(limit),
+                _ => {
+                    return Err(DeError::custom(format!(
+                        "Invalid candidates value '{count}' for field '{}'. Must be a whole number of at least 2",
+                        raw.field_name
+                    )));
+                }
+            },
+        };
+
+        let options = parse_options(&raw.options, &kind).map_err(|e| {
+            DeError::custom(format!("Field '{}': {e}", raw.field_name))
+        })?;
+
         Ok(Self {
             field_name: raw.field_name,
             description: raw.description,
             kind,
             infer,
+            batch: Some(raw.batch.trim().to_owned())
+                .filter(|key| !key.is_empty()),
+            guidance: Some(raw.guidance.trim().to_owned())
+                .filter(|guidance| !guidance.is_empty()),
+            when,
+            max_candidates,
+            options,
         })
     }
 }
 
-pub fn parse_schema_csv(csv_content: &str) -> Result<Vec<SchemaField>, String> {
+/// Splits an `options` cell on `|`. Only categorical fields may have
+/// options, and each must be non-empty and distinct.
+fn parse_options(cell: &str, kind: &SchemaKind) -> Result<Vec<String>, String> {
+    let trimmed = cell.trim();
+    if trimmed.is_empty() {
+        return Ok(Vec::new());
+    }
+    if !matches!(*kind, SchemaKind::Categorical) {
+        return Err(
+            "options are only allowed for categorical fields".to_owned()
+        );
+    }
+    let options: Vec<String> = trimmed
+        .split('|')
+        .map(|option| option.trim().to_owned())
+        .collect();
+    let mut seen = HashSet::new();
+    if let Some(invalid) = options
+        .iter()
+        .find(|option| option.is_empty() || !seen.insert(*option))
+    {
+        return Err(format!("option '{invalid}' is empty or repeated"));
+    }
+    Ok(options)
+}
+
+/// Parses a schema CSV, rejecting descriptions longer than
+/// `max_description_chars`.
+pub fn parse_schema_csv(
+    csv_content: &str,
+    max_description_chars: usize,
+) -> Result<Vec<SchemaField>, String> {
     let mut reader = Reader::from_reader(csv_content.as_bytes());
     let mut fields = Vec::new();
     let mut seen_names = HashSet::new();
@@ -112,6 +313,14 @@ pub fn parse_schema_csv(csv_content: &str) -> Result<Vec<SchemaField>, String> {
             format!("Failed to parse schema row {row_num}: {e}")
         })?;
 
+        let description_chars = field.description.chars().count();
+        if description_chars > max_description_chars {
+            return Err(format!(
+                "Description for field '{}' exceeds {max_description_chars} characters (length: {description_chars}); move the details to the guidance column",
+                field.field_name
+            ));
+        }
+
         // Check for duplicate field names
         if !seen_names.insert(field.field_name.clone()) {
             return Err(format!(
@@ -123,14 +332,126 @@ pub fn parse_schema_csv(csv_content: &str) -> Result<Vec<SchemaField>, String> {
         fields.push(field);
     }
 
+    for field in &fields {
+        if let Some(condition) = field.when.as_ref() {
+            check_condition(&fields, &field.field_name, condition)?;
+        }
+    }
+
     Ok(fields)
 }
 
-pub fn read_schema(path: &str) -> Vec<SchemaField> {
-    let file_content =
-        fs::read_to_string(path).expect("Failed to read schema file");
+/// Conditions may only test fields extracted in the first pass, that is
+/// existing fields without a condition of their own.
+fn check_condition(
+    fields: &[SchemaField],
+    field_name: &str,
+    condition: &Condition,
+) -> Result<(), String> {
+    let tested = fields
+        .iter()
+        .find(|other| other.field_name == condition.field)
+        .ok_or_else(|| {
+            format!(
+                "Condition for field '{field_name}' refers to unknown field '{}'",
+                condition.field
+            )
+        })?;
+    if tested.when.is_some() {
+        return Err(format!(
+            "Condition for field '{field_name}' refers to '{}', which has a condition of its own",
+            condition.field
+        ));
+    }
+    Ok(())
+}
 
-    parse_schema_csv(&file_content).unwrap_or_else(|e| panic!("{}", e))
+/// The fields to extract, in the order they appear in the schema CSV.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    fields: Vec<SchemaField>,
+}
+
+impl Schema {
+    /// Parses and validates the contents of a schema CSV.
+    ///
+    /// # Errors
+    ///
+    /// Fails on malformed rows, invalid kinds or infer values, duplicate
+    /// field names and descriptions over [`DEFAULT_MAX_DESCRIPTION_CHARS`].
+    #[inline]
+    pub fn parse(csv_content: &str) -> Result<Self, String> {
+        Self::parse_with_max_description(
+            csv_content,
+            DEFAULT_MAX_DESCRIPTION_CHARS,
+        )
+    }
+
+    /// Like [`Schema::parse`], with a different limit on description length.
+    ///
+    /// # Errors
+    ///
+    /// Fails on malformed rows, invalid kinds or infer values, duplicate
+    /// field names and descriptions over the limit.
+    #[inline]
+    pub fn parse_with_max_description(
+        csv_content: &str,
+        max_description_chars: usize,
+    ) -> Result<Self, String> {
+        parse_schema_csv(csv_content, max_description_chars).map(Self::from)
+    }
+
+    /// Reads and validates a schema CSV file.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file cannot be read or does not parse.
+    #[inline]
+    pub fn from_path<P>(path: P) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        Self::from_path_with_max_description(
+            path,
+            DEFAULT_MAX_DESCRIPTION_CHARS,
+        )
+    }
+
+    /// Like [`Schema::from_path`], with a different limit on description
+    /// length.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file cannot be read or does not parse.
+    #[inline]
+    pub fn from_path_with_max_description<P>(
+        path: P,
+        max_description_chars: usize,
+    ) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        let file_content = fs::read_to_string(path.as_ref()).map_err(|e| {
+            format!(
+                "Failed to read schema file {}: {e}",
+                path.as_ref().display()
+            )
+        })?;
+        Self::parse_with_max_description(&file_content, max_description_chars)
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn fields(&self) -> &[SchemaField] {
+        &self.fields
+    }
+}
+
+impl From<Vec<SchemaField>> for Schema {
+    #[inline]
+    fn from(fields: Vec<SchemaField>) -> Self {
+        Self { fields }
+    }
 }
 
 pub fn build_json_schema(fields: &[SchemaField]) -> Value {
@@ -143,37 +464,69 @@ pub fn build_json_schema(fields: &[SchemaField]) -> Value {
             SchemaKind::Categorical | SchemaKind::Text => "string",
         };
 
+        let mut value = json!({
+            "type": [field_type, "null"],
+            "description": field.description
+        });
+        let mut candidate_value = json!({"type": field_type});
+        if !field.options.is_empty() {
+            let mut allowed: Vec<Value> =
+                field.options.iter().map(|option| json!(option)).collect();
+            candidate_value["enum"] = Value::from(allowed.clone());
+            allowed.push(Value::Null);
+            value["enum"] = Value::from(allowed);
+        }
+
+        let mut candidates = json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "value": candidate_value,
+                    "page": {"type": ["integer", "null"]},
+                    "xmin": {"type": ["number", "null"]},
+                    "ymin": {"type": ["number", "null"]},
+                    "xmax": {"type": ["number", "null"]},
+                    "ymax": {"type": ["number", "null"]},
+                    "evidence": {"type": ["string", "null"]}
+                },
+                "required": ["value", "page", "xmin", "ymin", "xmax", "ymax", "evidence"],
+                "additionalProperties": false
+            }
+        });
+        if let Some(limit) = field.max_candidates {
+            candidates["maxItems"] = Value::from(limit);
+        }
+
         let field_schema = json!({
             "type": "object",
             "properties": {
-                "value": {
-                    "type": [field_type, "null"],
-                    "description": field.description
-                },
+                "value": value,
                 "match_type": {
                     "type": "string",
-                    "enum": ["found", "not_found", "inferred"]
+                    "enum": MATCH_TYPES
                 },
+                "candidates": candidates,
                 "comment": {
                     "type": ["string", "null"]
                 },
                 "page": {
-                    "type": "integer"
+                    "type": ["integer", "null"]
                 },
                 "xmin": {
-                    "type": "number"
+                    "type": ["number", "null"]
                 },
                 "ymin": {
-                    "type": "number"
+                    "type": ["number", "null"]
                 },
                 "xmax": {
-                    "type": "number"
+                    "type": ["number", "null"]
                 },
                 "ymax": {
-                    "type": "number"
+                    "type": ["number", "null"]
                 }
             },
-            "required": ["value", "match_type", "comment", "page", "xmin", "ymin", "xmax", "ymax"],
+            "required": ["value", "match_type", "candidates", "comment", "page", "xmin", "ymin", "xmax", "ymax"],
             "additionalProperties": false
         });
 
@@ -194,12 +547,12 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_valid_schema() {
+    fn valid_schema() {
         let csv = "field_name,description,kind,infer\n\
                    title,Paper title,text,false\n\
                    year,Publication year,number,true";
 
-        let result = parse_schema_csv(csv);
+        let result = parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS);
         assert!(result.is_ok());
         let fields = result.unwrap();
         assert_eq!(fields.len(), 2);
@@ -208,62 +561,222 @@ mod tests {
     }
 
     #[test]
-    fn test_field_name_too_long() {
+    fn field_name_too_long() {
         let csv = "field_name,description,kind,infer\n\
-                   this_field_name_17,Valid description,text,true";
+                   this_field_name_is_far_longer_than_forty_chars,Valid description,text,true";
 
-        let result = parse_schema_csv(csv);
+        let result = parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS);
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("exceeds 16 characters"));
     }
 
     #[test]
-    fn test_field_name_non_ascii() {
+    fn field_name_non_ascii() {
         let csv = "field_name,description,kind,infer\n\
-                   field_émoji,Valid description,text,true";
+                   field_\u{e9}moji,Valid description,text,true";
 
-        let result = parse_schema_csv(csv);
+        let result = parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS);
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("non-ASCII"));
     }
 
     #[test]
-    fn test_description_too_long() {
+    fn description_too_long() {
         let csv = "field_name,description,kind,infer\n\
-                   field,This description is way too long and exceeds one hundred characters which should trigger a validation error,text,false";
+                   field,This description is way too long and exceeds one hundred characters which should trigger a validation error once it passes the limit,text,false";
 
-        let result = parse_schema_csv(csv);
+        let result = parse_schema_csv(csv, 100);
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("exceeds 100 characters"));
+        Schema::parse(csv).unwrap();
     }
 
     #[test]
-    fn test_description_non_ascii() {
+    fn description_non_ascii() {
         let csv = "field_name,description,kind,infer\n\
-                   field,Description with émoji,text,true";
+                   dash,DASH score (mean \u{b1} SD) at 3 months,number,false";
 
-        let result = parse_schema_csv(csv);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("non-ASCII"));
+        let fields = parse_schema_csv(csv, 40).unwrap();
+        assert_eq!(
+            fields[0].description,
+            "DASH score (mean \u{b1} SD) at 3 months"
+        );
+    }
+
+    #[test]
+    fn guidance_column_is_optional() {
+        let csv = "field_name,description,kind,infer,guidance\n\
+                   dash_3m,DASH at 3 months,number,false,\"If only 12 weeks is reported, use that.\nIgnore QuickDASH.\"\n\
+                   title,Paper title,text,false,";
+
+        let fields =
+            parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS).unwrap();
+        assert_eq!(
+            fields[0].guidance.as_deref(),
+            Some("If only 12 weeks is reported, use that.\nIgnore QuickDASH.")
+        );
+        assert_eq!(fields[1].guidance, None);
+    }
+
+    #[test]
+    fn conditions_test_first_pass_fields() {
+        let csv = "field_name,description,kind,infer,when\n\
+                   arm,Intervention arm,text,false,\n\
+                   graft_volume,Graft volume,number,false,\"arm != \"\"No graft\"\"\"";
+
+        let fields =
+            parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS).unwrap();
+        let condition = fields[1].when.as_ref().unwrap();
+        assert_eq!(condition.to_string(), "arm != \"No graft\"");
+        assert!(condition.holds("Autograft"));
+        assert!(!condition.holds(" no graft "));
+        assert!(condition.holds(""));
+        assert!(Condition::parse("arm == yes").unwrap().holds("Yes"));
+
+        let unknown = "field_name,description,kind,infer,when\n\
+                       volume,Graft volume,number,false,graft == yes";
+        let missing = parse_schema_csv(unknown, DEFAULT_MAX_DESCRIPTION_CHARS)
+            .unwrap_err();
+        assert!(missing.contains("unknown field 'graft'"), "{missing}");
+
+        let chained = "field_name,description,kind,infer,when\n\
+                       arm,Intervention arm,text,false,\n\
+                       graft,Graft type,text,false,arm == graft\n\
+                       volume,Graft volume,number,false,graft == bone";
+        let nested = parse_schema_csv(chained, DEFAULT_MAX_DESCRIPTION_CHARS)
+            .unwrap_err();
+        assert!(nested.contains("condition of its own"), "{nested}");
+
+        let invalid = Condition::parse("arm = yes").unwrap_err();
+        assert!(invalid.contains("FIELD =="), "{invalid}");
+    }
+
+    #[test]
+    fn candidates_column_limits_the_list() {
+        let csv = "field_name,description,kind,infer,candidates\n\
+                   n,Sample size,number,false,3\n\
+                   title,Paper title,text,false,";
+
+        let fields =
+            parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS).unwrap();
+        assert_eq!(fields[0].max_candidates, Some(3));
+        assert_eq!(fields[1].max_candidates, None);
+
+        let schema = build_json_schema(&fields);
+        let properties = &schema["properties"];
+        assert_eq!(
+            properties["n"]["properties"]["candidates"]["maxItems"].as_u64(),
+            Some(3)
+        );
+        assert!(
+            properties["title"]["properties"]["candidates"]
+                .get("maxItems")
+                .is_none()
+        );
+        assert_eq!(
+            properties["n"]["properties"]["candidates"]["items"]["properties"]
+                ["value"]["type"],
+            "number"
+        );
+
+        let single = "field_name,description,kind,infer,candidates\n\
+                      n,Sample size,number,false,1";
+        let error = parse_schema_csv(single, DEFAULT_MAX_DESCRIPTION_CHARS)
+            .unwrap_err();
+        assert!(error.contains("at least 2"), "{error}");
+    }
+
+    #[test]
+    fn options_restrict_categorical_answers() {
+        let csv = "field_name,description,kind,infer,options\n\
+                   sequence,Was the allocation sequence random?,categorical,false,Y | PY | PN | N | NI\n\
+                   title,Paper title,text,false,";
+
+        let fields =
+            parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS).unwrap();
+        assert_eq!(fields[0].options, ["Y", "PY", "PN", "N", "NI"]);
+        assert!(fields[1].options.is_empty());
+
+        let schema = build_json_schema(&fields);
+        let sequence = &schema["properties"]["sequence"]["properties"];
+        assert_eq!(
+            sequence["value"]["enum"],
+            json!(["Y", "PY", "PN", "N", "NI", null])
+        );
+        assert_eq!(
+            sequence["candidates"]["items"]["properties"]["value"]["enum"],
+            json!(["Y", "PY", "PN", "N", "NI"])
+        );
+        assert!(
+            schema["properties"]["title"]["properties"]["value"]
+                .get("enum")
+                .is_none()
+        );
+
+        let number = "field_name,description,kind,infer,options\n\
+                      n,Sample size,number,false,1|2";
+        let not_categorical =
+            parse_schema_csv(number, DEFAULT_MAX_DESCRIPTION_CHARS)
+                .unwrap_err();
+        assert!(
+            not_categorical.contains("only allowed for categorical"),
+            "{not_categorical}"
+        );
+
+        let repeated = "field_name,description,kind,infer,options\n\
+                        arm,Arm,categorical,false,graft|graft";
+        let duplicate =
+            parse_schema_csv(repeated, DEFAULT_MAX_DESCRIPTION_CHARS)
+                .unwrap_err();
+        assert!(duplicate.contains("repeated"), "{duplicate}");
+    }
+
+    #[test]
+    fn schema_keeps_field_order() {
+        let csv = "field_name,description,kind,infer\n\
+                   year,Publication year,number,false\n\
+                   title,Paper title,text,false";
+
+        let schema = Schema::parse(csv).unwrap();
+        let names: Vec<&str> = schema
+            .fields()
+            .iter()
+            .map(|field| field.field_name.as_str())
+            .collect();
+        assert_eq!(names, ["year", "title"]);
+        let missing = Schema::from_path("missing-schema.csv");
+        assert!(missing.unwrap_err().contains("missing-schema.csv"));
+    }
+
+    #[test]
+    fn batch_column_is_optional() {
+        let csv = "field_name,description,kind,infer,batch\n\
+                   dash_6w,DASH at 6 weeks,number,false,dash\n\
+                   title,Paper title,text,false,";
+
+        let fields =
+            parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS).unwrap();
+        assert_eq!(fields[0].batch.as_deref(), Some("dash"));
+        assert_eq!(fields[1].batch, None);
     }
 
     #[test]
-    fn test_duplicate_field_names() {
+    fn duplicate_field_names() {
         let csv = "field_name,description,kind,infer\n\
                    duplicate,First description,text,true\n\
                    duplicate,Second description,number,false";
 
-        let result = parse_schema_csv(csv);
+        let result = parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS);
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("Duplicate field name"));
     }
 
     #[test]
-    fn test_invalid_kind() {
+    fn invalid_kind() {
         let csv = "field_name,description,kind,infer\n\
                    field,Valid description,invalid_type,true";
 
-        let result = parse_schema_csv(csv);
+        let result = parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS);
         assert!(result.is_err());
         let error_msg = result.unwrap_err();
         assert!(error_msg.contains("Invalid schema kind"));
@@ -271,11 +784,11 @@ mod tests {
     }
 
     #[test]
-    fn test_invalid_infer() {
+    fn invalid_infer() {
         let csv = "field_name,description,kind,infer\n\
                    field,Valid description,text,maybe";
 
-        let result = parse_schema_csv(csv);
+        let result = parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS);
         assert!(result.is_err());
         let error_msg = result.unwrap_err();
         assert!(error_msg.contains("Invalid infer value"));
@@ -283,20 +796,20 @@ mod tests {
     }
 
     #[test]
-    fn test_valid_infer_values() {
+    fn valid_infer_values() {
         let csv = "field_name,description,kind,infer\n\
                    field1,Desc,text,true\n\
                    field2,Desc,text,false";
 
-        let result = parse_schema_csv(csv);
+        let result = parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS);
         assert!(result.is_ok());
         let fields = result.unwrap();
-        assert_eq!(fields[0].infer, true);
-        assert_eq!(fields[1].infer, false);
+        assert!(fields[0].infer);
+        assert!(!fields[1].infer);
     }
 
     #[test]
-    fn test_invalid_infer_values() {
+    fn invalid_infer_values() {
         let test_cases = vec![
             "yes", "no", "1", "0", "y", "n", "on", "off", "TRUE", "FALSE",
             "True", "False",
@@ -304,14 +817,12 @@ mod tests {
 
         for invalid_value in test_cases {
             let csv = format!(
-                "field_name,description,kind,infer\nfield,Desc,text,{}",
-                invalid_value
+                "field_name,description,kind,infer\nfield,Desc,text,{invalid_value}"
             );
-            let result = parse_schema_csv(&csv);
+            let result = parse_schema_csv(&csv, DEFAULT_MAX_DESCRIPTION_CHARS);
             assert!(
                 result.is_err(),
-                "Should reject infer value: {}",
-                invalid_value
+                "Should reject infer value: {invalid_value}"
             );
             let error_msg = result.unwrap_err();
             assert!(error_msg.contains("Invalid infer value"));
@@ -322,19 +833,17 @@ mod tests {
     }
 
     #[test]
-    fn test_invalid_uppercase_kind() {
+    fn invalid_uppercase_kind() {
         let test_cases = vec!["TEXT", "Number", "CATEGORICAL", "Categorical"];
 
         for invalid_kind in test_cases {
             let csv = format!(
-                "field_name,description,kind,infer\nfield,Desc,{},true",
-                invalid_kind
+                "field_name,description,kind,infer\nfield,Desc,{invalid_kind},true"
             );
-            let result = parse_schema_csv(&csv);
+            let result = parse_schema_csv(&csv, DEFAULT_MAX_DESCRIPTION_CHARS);
             assert!(
                 result.is_err(),
-                "Should reject kind value: {}",
-                invalid_kind
+                "Should reject kind value: {invalid_kind}"
             );
             let error_msg = result.unwrap_err();
             assert!(error_msg.contains("Invalid schema kind"));
@@ -346,13 +855,13 @@ mod tests {
     }
 
     #[test]
-    fn test_valid_lowercase_kind() {
+    fn valid_lowercase_kind() {
         let csv = "field_name,description,kind,infer\n\
                    field1,Desc,text,true\n\
                    field2,Desc,number,false\n\
                    field3,Desc,categorical,true";
 
-        let result = parse_schema_csv(csv);
+        let result = parse_schema_csv(csv, DEFAULT_MAX_DESCRIPTION_CHARS);
         assert!(result.is_ok());
         let fields = result.unwrap();
         assert!(matches!(fields[0].kind, SchemaKind::Text));
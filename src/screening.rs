@@ -0,0 +1,635 @@
+use crate::Extraction;
+use crate::extraction::{ExtractedField, value_text};
+use crate::schema::{Schema, SchemaField, SchemaKind};
+use core::fmt;
+use csv::{Reader, Writer};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Passages the model may quote as evidence for one criterion.
+const MAX_EVIDENCE: usize = 3;
+
+/// Longest criterion name accepted; names are field names in the request.
+const MAX_NAME_CHARS: usize = 40;
+
+/// Whether meeting a criterion makes a paper eligible or rules it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CriterionType {
+    Include,
+    Exclude,
+}
+
+/// One eligibility criterion from the criteria CSV.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Criterion {
+    pub name: String,
+    pub description: String,
+    pub kind: CriterionType,
+}
+
+impl Criterion {
+    /// Whether `verdict` rules the paper out.
+    fn excludes(&self, verdict: Verdict) -> bool {
+        match self.kind {
+            CriterionType::Include => verdict == Verdict::NotMet,
+            CriterionType::Exclude => verdict == Verdict::Met,
+        }
+    }
+
+    /// Whether `verdict` leaves the paper eligible.
+    fn admits(&self, verdict: Verdict) -> bool {
+        match self.kind {
+            CriterionType::Include => verdict == Verdict::Met,
+            CriterionType::Exclude => verdict == Verdict::NotMet,
+        }
+    }
+
+    /// The request field that asks for this criterion's verdict, with each
+    /// deciding passage as a candidate.
+    fn schema_field(&self) -> SchemaField {
+        let effect = match self.kind {
+            CriterionType::Include => {
+                "Inclusion criterion: a paper that does not meet it is excluded."
+            }
+            CriterionType::Exclude => {
+                "Exclusion criterion: a paper that meets it is excluded."
+            }
+        };
+        SchemaField {
+            field_name: self.name.clone(),
+            description: self.description.clone(),
+            kind: SchemaKind::Categorical,
+            infer: true,
+            batch: None,
+            guidance: Some(format!(
+                "{effect}\nList the passages that decide it in candidates, each with its own answer as value and the sentence as evidence."
+            )),
+            when: None,
+            max_candidates: Some(MAX_EVIDENCE),
+            options: [Verdict::Met, Verdict::NotMet, Verdict::Unclear]
+                .map(|verdict| verdict.name().to_owned())
+                .into(),
+        }
+    }
+}
+
+/// The eligibility criteria of a review, in the order of the criteria CSV.
+#[derive(Debug, Clone)]
+pub struct Criteria {
+    criteria: Vec<Criterion>,
+}
+
+impl Criteria {
+    /// Parses a criteria CSV with `name`, `description` and `type` columns,
+    /// where `type` is `include` or `exclude`.
+    ///
+    /// # Errors
+    ///
+    /// Fails on malformed rows, invalid types, names that cannot be used as
+    /// field names, duplicate names and a file without criteria.
+    #[inline]
+    pub fn parse(csv_content: &str) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct RawCriterion {
+            name: String,
+            description: String,
+            #[serde(rename = "type")]
+            kind: String,
+        }
+
+        let mut reader = Reader::from_reader(csv_content.as_bytes());
+        let mut criteria = Vec::new();
+        let mut seen_names = HashSet::new();
+
+        for (index, result) in reader.deserialize().enumerate() {
+            let row_num = index.saturating_add(2);
+            let raw: RawCriterion = result.map_err(|e| {
+                format!("Failed to parse criteria row {row_num}: {e}")
+            })?;
+            let name = raw.name.trim().to_owned();
+            if name.is_empty()
+                || !name.is_ascii()
+                || name.len() > MAX_NAME_CHARS
+            {
+                return Err(format!(
+                    "Invalid criterion name '{name}' at row {row_num}. Must be 1-{MAX_NAME_CHARS} ASCII characters"
+                ));
+            }
+            let kind = match raw.kind.trim() {
+                "include" => CriterionType::Include,
+                "exclude" => CriterionType::Exclude,
+                other => {
+                    return Err(format!(
+                        "Invalid type '{other}' for criterion '{name}'. Must be include or exclude (lowercase only)"
+                    ));
+                }
+            };
+            if !seen_names.insert(name.clone()) {
+                return Err(format!(
+                    "Duplicate criterion name '{name}' found at row {row_num}"
+                ));
+            }
+            criteria.push(Criterion {
+                name,
+                description: raw.description.trim().to_owned(),
+                kind,
+            });
+        }
+
+        if criteria.is_empty() {
+            return Err("The criteria file lists no criteria".to_owned());
+        }
+        Ok(Self { criteria })
+    }
+
+    /// Reads and validates a criteria CSV file.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file cannot be read or does not parse.
+    #[inline]
+    pub fn from_path<P>(path: P) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        let file_content = fs::read_to_string(path.as_ref()).map_err(|e| {
+            format!(
+                "Failed to read criteria file {}: {e}",
+                path.as_ref().display()
+            )
+        })?;
+        Self::parse(&file_content)
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn criteria(&self) -> &[Criterion] {
+        &self.criteria
+    }
+
+    /// The schema to extract, one categorical field per criterion, so
+    /// screening goes through the same requests as extraction.
+    #[inline]
+    pub fn schema(&self) -> Schema {
+        Schema::from(
+            self.criteria
+                .iter()
+                .map(Criterion::schema_field)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Decides on one paper from the verdicts in its extraction.
+    ///
+    /// A paper is excluded when it fails an inclusion criterion or meets an
+    /// exclusion criterion, and included when every verdict leaves it
+    /// eligible. Anything else is left for a person to decide.
+    #[must_use]
+    #[inline]
+    pub fn screen(&self, paper: &str, extraction: &Extraction) -> Screening {
+        let verdicts: Vec<CriterionVerdict> = self
+            .criteria
+            .iter()
+            .map(|criterion| {
+                CriterionVerdict::new(
+                    &criterion.name,
+                    extraction.get(&criterion.name),
+                )
+            })
+            .collect();
+        let reasons: Vec<String> = self
+            .criteria
+            .iter()
+            .zip(&verdicts)
+            .filter(|&(criterion, judged)| criterion.excludes(judged.verdict))
+            .map(|(criterion, _)| criterion.name.clone())
+            .collect();
+        let decision = if !reasons.is_empty() {
+            Decision::Exclude
+        } else if self
+            .criteria
+            .iter()
+            .zip(&verdicts)
+            .all(|(criterion, judged)| criterion.admits(judged.verdict))
+        {
+            Decision::Include
+        } else {
+            Decision::Unclear
+        };
+        Screening {
+            paper: paper.to_owned(),
+            decision,
+            reasons,
+            verdicts,
+        }
+    }
+}
+
+/// The model's answer on one criterion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Verdict {
+    Met,
+    NotMet,
+    Unclear,
+}
+
+impl Verdict {
+    #[must_use]
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Met => "met",
+            Self::NotMet => "not_met",
+            Self::Unclear => "unclear",
+        }
+    }
+
+    /// Reads a verdict from a field's answer. Anything other than a found or
+    /// inferred `met` or `not_met`, such as conflicting passages, is unclear.
+    fn from_field(field: &ExtractedField) -> Self {
+        if !matches!(field.match_type.as_str(), "found" | "inferred") {
+            return Self::Unclear;
+        }
+        let answer = field
+            .value
+            .as_ref()
+            .map(value_text)
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase()
+            .replace([' ', '-'], "_");
+        match answer.as_str() {
+            "met" | "yes" | "true" => Self::Met,
+            "not_met" | "no" | "false" => Self::NotMet,
+            _ => Self::Unclear,
+        }
+    }
+}
+
+/// A criterion's verdict for one paper and the text it rests on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CriterionVerdict {
+    pub criterion: String,
+    pub verdict: Verdict,
+    /// The quoted passages with their pages, or the model's comment when it
+    /// quoted none.
+    pub evidence: String,
+    pub page: Option<u32>,
+}
+
+impl CriterionVerdict {
+    fn new(criterion: &str, field: Option<&ExtractedField>) -> Self {
+        let Some(answer) = field else {
+            return Self {
+                criterion: criterion.to_owned(),
+                verdict: Verdict::Unclear,
+                evidence: String::new(),
+                page: None,
+            };
+        };
+        let quotes: Vec<String> = answer
+            .candidates
+            .iter()
+            .filter_map(|candidate| {
+                let quote = candidate.evidence.as_deref()?;
+                Some(candidate.page.map_or_else(
+                    || format!("\"{quote}\""),
+                    |page| format!("\"{quote}\" (page {page})"),
+                ))
+            })
+            .collect();
+        let evidence = if quotes.is_empty() {
+            answer.comment.clone().unwrap_or_default()
+        } else {
+            quotes.join("; ")
+        };
+        Self {
+            criterion: criterion.to_owned(),
+            verdict: Verdict::from_field(answer),
+            evidence,
+            page: answer.page,
+        }
+    }
+}
+
+/// What screening decided for a paper.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Decision {
+    Include,
+    Exclude,
+    /// The verdicts neither rule the paper out nor admit it, so a person
+    /// decides, usually from the full text.
+    Unclear,
+}
+
+impl Decision {
+    #[must_use]
+    #[inline]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Include => "include",
+            Self::Exclude => "exclude",
+            Self::Unclear => "unclear",
+        }
+    }
+}
+
+/// The screening decision for one paper, with the criteria that excluded it
+/// and every verdict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Screening {
+    pub paper: String,
+    pub decision: Decision,
+    /// Criteria that ruled the paper out, in criteria file order.
+    pub reasons: Vec<String>,
+    pub verdicts: Vec<CriterionVerdict>,
+}
+
+/// Writes one row per paper: its decision, the reasons for an exclusion and
+/// each criterion's verdict, evidence and page.
+///
+/// # Errors
+///
+/// Fails when the file cannot be written.
+#[inline]
+pub fn write_screening_csv(
+    path: &str,
+    criteria: &Criteria,
+    screenings: &[Screening],
+) -> Result<(), String> {
+    let write_error = |e: csv::Error| format!("Failed to write {path}: {e}");
+    let mut writer = Writer::from_path(path).map_err(write_error)?;
+
+    let mut headers = vec![
+        "paper".to_owned(),
+        "decision".to_owned(),
+        "reasons".to_owned(),
+    ];
+    for criterion in criteria.criteria() {
+        headers.push(criterion.name.clone());
+        headers.push(format!("{}_evidence", criterion.name));
+        headers.push(format!("{}_page", criterion.name));
+    }
+    writer.write_record(&headers).map_err(write_error)?;
+
+    for screening in screenings {
+        let mut row = vec![
+            screening.paper.clone(),
+            screening.decision.name().to_owned(),
+            screening.reasons.join("; "),
+        ];
+        for judged in &screening.verdicts {
+            row.push(judged.verdict.name().to_owned());
+            row.push(judged.evidence.clone());
+            row.push(
+                judged.page.map(|page| page.to_string()).unwrap_or_default(),
+            );
+        }
+        writer.write_record(&row).map_err(write_error)?;
+    }
+
+    writer
+        .flush()
+        .map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+/// PRISMA-style counts of the screening decisions across a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PrismaCounts {
+    pub screened: usize,
+    pub included: usize,
+    pub excluded: usize,
+    pub unclear: usize,
+    /// Exclusions by criterion, in criteria file order. Each excluded paper
+    /// is counted once, under the first criterion that ruled it out.
+    pub reasons: Vec<(String, usize)>,
+}
+
+impl PrismaCounts {
+    #[must_use]
+    #[inline]
+    pub fn new(criteria: &Criteria) -> Self {
+        Self {
+            screened: 0,
+            included: 0,
+            excluded: 0,
+            unclear: 0,
+            reasons: criteria
+                .criteria()
+                .iter()
+                .map(|criterion| (criterion.name.clone(), 0))
+                .collect(),
+        }
+    }
+
+    #[inline]
+    pub fn add(&mut self, screening: &Screening) {
+        self.screened = self.screened.saturating_add(1);
+        let count = match screening.decision {
+            Decision::Include => &mut self.included,
+            Decision::Exclude => &mut self.excluded,
+            Decision::Unclear => &mut self.unclear,
+        };
+        *count = count.saturating_add(1);
+        if let Some(first) = screening.reasons.first()
+            && let Some(reason) =
+                self.reasons.iter_mut().find(|reason| reason.0 == *first)
+        {
+            reason.1 = reason.1.saturating_add(1);
+        }
+    }
+
+    /// Writes the counts as `stage,count` rows, with a row per exclusion
+    /// reason.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file cannot be written.
+    #[inline]
+    pub fn write_csv(&self, path: &str) -> Result<(), String> {
+        let write_error =
+            |e: csv::Error| format!("Failed to write {path}: {e}");
+        let mut writer = Writer::from_path(path).map_err(write_error)?;
+        writer
+            .write_record(["stage", "count"])
+            .map_err(write_error)?;
+        let mut rows = vec![
+            ("Records screened".to_owned(), self.screened),
+            ("Records excluded".to_owned(), self.excluded),
+        ];
+        rows.extend(self.reasons.iter().map(|reason| {
+            (format!("Records excluded: {}", reason.0), reason.1)
+        }));
+        rows.push(("Records included".to_owned(), self.included));
+        rows.push(("Records unclear".to_owned(), self.unclear));
+        for (stage, count) in rows {
+            writer
+                .write_record([stage, count.to_string()])
+                .map_err(write_error)?;
+        }
+        writer
+            .flush()
+            .map_err(|e| format!("Failed to write {path}: {e}"))
+    }
+}
+
+impl fmt::Display for PrismaCounts {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Records screened: {}", self.screened)?;
+        writeln!(f, "Records excluded: {}", self.excluded)?;
+        for reason in self.reasons.iter().filter(|reason| reason.1 > 0) {
+            writeln!(f, "  {}: {}", reason.0, reason.1)?;
+        }
+        writeln!(f, "Records included: {}", self.included)?;
+        write!(f, "Records unclear, for full-text review: {}", self.unclear)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extraction::Candidate;
+    use serde_json::Value;
+
+    const CRITERIA: &str = "name,description,type\n\
+                            adults,Participants are adults,include\n\
+                            rct,Randomised controlled trial,include\n\
+                            animal,Animal study,exclude";
+
+    fn answer(value: &str, match_type: &str) -> ExtractedField {
+        ExtractedField {
+            value: Some(Value::from(value)),
+            match_type: match_type.to_owned(),
+            comment: None,
+            page: None,
+            bbox: None,
+            warnings: Vec::new(),
+            candidates: Vec::new(),
+        }
+    }
+
+    fn extraction(answers: [(&str, &str, &str); 3]) -> Extraction {
+        answers
+            .into_iter()
+            .map(|(name, value, match_type)| {
+                (name.to_owned(), answer(value, match_type))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn criteria_become_categorical_fields_with_evidence() {
+        let criteria = Criteria::parse(CRITERIA).unwrap();
+        let schema = criteria.schema();
+        let fields = schema.fields();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[2].field_name, "animal");
+        assert_eq!(fields[2].max_candidates, Some(MAX_EVIDENCE));
+        assert_eq!(fields[2].options, ["met", "not_met", "unclear"]);
+        assert!(
+            fields[2]
+                .guidance
+                .as_deref()
+                .unwrap()
+                .starts_with("Exclusion criterion")
+        );
+
+        let wrong_type = "name,description,type\nadults,Adults,Include";
+        let error = Criteria::parse(wrong_type).unwrap_err();
+        assert!(error.contains("include or exclude"), "{error}");
+
+        let duplicate = "name,description,type\na,A,include\na,B,exclude";
+        let repeated = Criteria::parse(duplicate).unwrap_err();
+        assert!(repeated.contains("Duplicate"), "{repeated}");
+
+        let empty = Criteria::parse("name,description,type\n").unwrap_err();
+        assert!(empty.contains("no criteria"), "{empty}");
+    }
+
+    #[test]
+    fn decisions_follow_the_criteria() {
+        let criteria = Criteria::parse(CRITERIA).unwrap();
+        let mut counts = PrismaCounts::new(&criteria);
+
+        let eligible = criteria.screen(
+            "a.pdf",
+            &extraction([
+                ("adults", "met", "found"),
+                ("rct", "Met", "inferred"),
+                ("animal", "not_met", "found"),
+            ]),
+        );
+        assert_eq!(eligible.decision, Decision::Include);
+        counts.add(&eligible);
+
+        let ruled_out = criteria.screen(
+            "b.pdf",
+            &extraction([
+                ("adults", "met", "found"),
+                ("rct", "not met", "found"),
+                ("animal", "met", "found"),
+            ]),
+        );
+        assert_eq!(ruled_out.decision, Decision::Exclude);
+        assert_eq!(ruled_out.reasons, ["rct", "animal"]);
+        counts.add(&ruled_out);
+
+        let undecided = criteria.screen(
+            "c.pdf",
+            &extraction([
+                ("adults", "met", "ambiguous"),
+                ("rct", "met", "found"),
+                ("animal", "not_met", "found"),
+            ]),
+        );
+        assert_eq!(undecided.decision, Decision::Unclear);
+        assert_eq!(undecided.verdicts[0].verdict, Verdict::Unclear);
+        counts.add(&undecided);
+
+        assert_eq!(
+            (
+                counts.screened,
+                counts.included,
+                counts.excluded,
+                counts.unclear
+            ),
+            (3, 1, 1, 1)
+        );
+        assert_eq!(counts.reasons[1], ("rct".to_owned(), 1));
+        assert_eq!(counts.reasons[2], ("animal".to_owned(), 0));
+        assert!(counts.to_string().contains("  rct: 1\n"));
+    }
+
+    #[test]
+    fn evidence_quotes_the_candidates() {
+        let mut field = answer("met", "found");
+        field.comment = Some("Adults only".to_owned());
+        assert_eq!(
+            CriterionVerdict::new("adults", Some(&field)).evidence,
+            "Adults only"
+        );
+
+        field.candidates = vec![Candidate {
+            value: Value::from("met"),
+            page: Some(2),
+            bbox: None,
+            evidence: Some("Patients aged 18 to 65".to_owned()),
+        }];
+        let judged = CriterionVerdict::new("adults", Some(&field));
+        assert_eq!(judged.evidence, "\"Patients aged 18 to 65\" (page 2)");
+        assert_eq!(
+            CriterionVerdict::new("adults", None).verdict,
+            Verdict::Unclear
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use core::fmt;
+use serde::Deserialize;
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt as _;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Shown in place of an API key.
+const REDACTED: &str = "[redacted]";
+
+/// A provider API key. `Debug` never shows it, and [`ApiKey::redact`]
+/// removes it from text that may echo it back, such as a raw response.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub struct ApiKey(Arc<str>);
+
+impl ApiKey {
+    #[inline]
+    pub fn new<S>(key: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self(Arc::from(key.into().trim()))
+    }
+
+    /// Reads a key from a file that holds nothing else. The file must only
+    /// be readable by its owner.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file can be read by others, cannot be read or is empty.
+    #[inline]
+    pub fn from_file<P>(path: P) -> Result<Self, String>
+    where
+        P: AsRef<Path>,
+    {
+        check_private(path.as_ref())?;
+        let key = fs::read_to_string(path.as_ref()).map_err(|e| {
+            format!(
+                "Failed to read API key file {}: {e}",
+                path.as_ref().display()
+            )
+        })?;
+        if key.trim().is_empty() {
+            return Err(format!(
+                "API key file {} is empty",
+                path.as_ref().display()
+            ));
+        }
+        Ok(Self::new(key))
+    }
+
+    /// The key itself, for the `Authorization` header.
+    #[must_use]
+    #[inline]
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Replaces every occurrence of the key in `text`.
+    #[must_use]
+    #[inline]
+    pub fn redact(&self, text: &str) -> String {
+        if self.0.is_empty() {
+            return text.to_owned();
+        }
+        text.replace(&*self.0, REDACTED)
+    }
+}
+
+impl From<String> for ApiKey {
+    #[inline]
+    fn from(key: String) -> Self {
+        Self::new(key)
+    }
+}
+
+impl From<&str> for ApiKey {
+    #[inline]
+    fn from(key: &str) -> Self {
+        Self::new(key)
+    }
+}
+
+impl fmt::Debug for ApiKey {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// Fails if anyone but its owner may read `path`, since a key in it would
+/// be exposed to every other user of the machine.
+#[cfg(unix)]
+pub fn check_private(path: &Path) -> Result<(), String> {
+    let metadata = fs::metadata(path)
+        .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let mode = metadata.permissions().mode();
+    if mode & 0o077 != 0 {
+        return Err(format!(
+            "{} holds an API key but can be read by other users (mode {:o}); run `chmod 600 {}`",
+            path.display(),
+            mode & 0o777,
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// File permissions are only checked on Unix.
+#[cfg(not(unix))]
+pub const fn check_private(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDir;
+    use std::fs::Permissions;
+
+    #[test]
+    fn keys_are_hidden_from_logs() {
+        let key = ApiKey::new("sk-or-v1-secret\n");
+        assert_eq!(key.expose(), "sk-or-v1-secret");
+        assert_eq!(format!("{key:?}"), "[redacted]");
+        assert_eq!(
+            key.redact(
+                r#"{"error":{"message":"Invalid key sk-or-v1-secret"}}"#
+            ),
+            r#"{"error":{"message":"Invalid key [redacted]"}}"#
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn key_files_must_be_private() {
+        let dir = TestDir::new("secret");
+        let path = dir.join("api.key");
+        fs::write(&path, "sk-test\n").unwrap();
+
+        fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+        let error = ApiKey::from_file(&path).unwrap_err();
+        assert!(error.contains("chmod 600"), "{error}");
+
+        fs::set_permissions(&path, Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(ApiKey::from_file(&path).unwrap().expose(), "sk-test");
+    }
+}
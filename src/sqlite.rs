@@ -0,0 +1,428 @@
+use crate::extraction::{ExtractedField, value_text};
+use crate::schema::SchemaField;
+use chrono::{SecondsFormat, Utc};
+use rusqlite::{Connection, params};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+const CREATE_TABLES: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    started_at TEXT NOT NULL,
+    finished_at TEXT,
+    model TEXT NOT NULL,
+    schema_path TEXT NOT NULL,
+    input TEXT NOT NULL,
+    input_mode TEXT NOT NULL,
+    prompt_hash TEXT
+);
+CREATE TABLE IF NOT EXISTS fields (
+    name TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    infer INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS papers (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    page_count INTEGER NOT NULL,
+    run_id INTEGER NOT NULL REFERENCES runs (id),
+    status TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS extractions (
+    paper_id INTEGER NOT NULL REFERENCES papers (id),
+    field_name TEXT NOT NULL REFERENCES fields (name),
+    value TEXT,
+    value_json TEXT,
+    match_type TEXT NOT NULL,
+    comment TEXT,
+    page INTEGER,
+    xmin REAL,
+    ymin REAL,
+    xmax REAL,
+    ymax REAL,
+    warnings TEXT NOT NULL,
+    candidates TEXT NOT NULL,
+    run_id INTEGER NOT NULL REFERENCES runs (id),
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (paper_id, field_name)
+);
+";
+
+const UPSERT_FIELD: &str = "
+INSERT INTO fields (name, position, description, kind, infer)
+VALUES (?1, ?2, ?3, ?4, ?5)
+ON CONFLICT (name) DO UPDATE SET
+    position = excluded.position,
+    description = excluded.description,
+    kind = excluded.kind,
+    infer = excluded.infer";
+
+const UPSERT_PAPER: &str = "
+INSERT INTO papers (path, name, page_count, run_id, status)
+VALUES (?1, ?2, ?3, ?4, 'running')
+ON CONFLICT (path) DO UPDATE SET
+    name = excluded.name,
+    page_count = excluded.page_count,
+    run_id = excluded.run_id,
+    status = 'running'
+RETURNING id";
+
+/// Drops a paper's answers for fields no longer in the schema, given as a
+/// JSON array of names.
+const DELETE_REMOVED_EXTRACTIONS: &str = "
+DELETE FROM extractions
+WHERE paper_id = ?1 AND field_name NOT IN (SELECT value FROM json_each(?2))";
+
+/// Drops fields that are no longer in the schema and that no paper has an
+/// answer for any more.
+const DELETE_UNUSED_FIELDS: &str = "
+DELETE FROM fields
+WHERE name NOT IN (SELECT value FROM json_each(?1))
+    AND name NOT IN (SELECT field_name FROM extractions)";
+
+const UPSERT_EXTRACTION: &str = "
+INSERT INTO extractions (
+    paper_id, field_name, value, value_json, match_type, comment,
+    page, xmin, ymin, xmax, ymax, warnings, candidates, run_id, updated_at
+)
+VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+ON CONFLICT (paper_id, field_name) DO UPDATE SET
+    value = excluded.value,
+    value_json = excluded.value_json,
+    match_type = excluded.match_type,
+    comment = excluded.comment,
+    page = excluded.page,
+    xmin = excluded.xmin,
+    ymin = excluded.ymin,
+    xmax = excluded.xmax,
+    ymax = excluded.ymax,
+    warnings = excluded.warnings,
+    candidates = excluded.candidates,
+    run_id = excluded.run_id,
+    updated_at = excluded.updated_at";
+
+/// What a run was started with, recorded in the `runs` table.
+#[expect(
+    clippy::exhaustive_structs,
+    reason = "built by the caller for each run, so every field must be given"
+)]
+pub struct RunInfo<'a> {
+    pub model: &'a str,
+    pub schema_path: &'a str,
+    pub input: &'a str,
+    pub input_mode: &'a str,
+    /// SHA-256 of the prompt template.
+    pub prompt_hash: &'a str,
+}
+
+/// Writes extraction results into a `SQLite` database as they arrive, so a
+/// run that stops halfway can still be queried and a re-run updates the
+/// same rows.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "the binary holds it next to `OutputSink`"
+)]
+pub struct SqliteSink {
+    connection: Mutex<Connection>,
+    run_id: i64,
+    /// The schema's field names as a JSON array.
+    field_names: String,
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+impl SqliteSink {
+    /// Opens or creates the database, records the schema fields and starts
+    /// a new run.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the database cannot be opened, its tables cannot be
+    /// created, or the run cannot be recorded.
+    #[inline]
+    pub fn open(
+        path: &Path,
+        fields: &[SchemaField],
+        run: &RunInfo,
+    ) -> Result<Self, String> {
+        let mut connection = Connection::open(path)
+            .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+        connection
+            .execute_batch(CREATE_TABLES)
+            .map_err(|e| format!("Failed to create tables: {e}"))?;
+
+        let transaction = connection
+            .transaction()
+            .map_err(|e| format!("Failed to write fields: {e}"))?;
+        for (index, field) in fields.iter().enumerate() {
+            let position = i64::try_from(index).map_err(|e| e.to_string())?;
+            transaction
+                .execute(
+                    UPSERT_FIELD,
+                    params![
+                        field.field_name,
+                        position,
+                        field.description,
+                        field.kind.name(),
+                        field.infer
+                    ],
+                )
+                .map_err(|e| format!("Failed to write fields: {e}"))?;
+        }
+        transaction
+            .commit()
+            .map_err(|e| format!("Failed to write fields: {e}"))?;
+
+        connection
+            .execute(
+                "INSERT INTO runs (started_at, model, schema_path, input, input_mode, prompt_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    now(),
+                    run.model,
+                    run.schema_path,
+                    run.input,
+                    run.input_mode,
+                    run.prompt_hash
+                ],
+            )
+            .map_err(|e| format!("Failed to record run: {e}"))?;
+        let run_id = connection.last_insert_rowid();
+        let names: Vec<&str> = fields
+            .iter()
+            .map(|field| field.field_name.as_str())
+            .collect();
+        let field_names =
+            serde_json::to_string(&names).map_err(|e| e.to_string())?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+            run_id,
+            field_names,
+        })
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        self.connection
+            .lock()
+            .map_err(|e| format!("Database connection poisoned: {e}"))
+    }
+
+    /// Registers a paper as being processed by this run and returns its id.
+    /// Answers stored for the paper by an earlier run for fields that have
+    /// since left the schema are removed.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the paper cannot be recorded.
+    #[expect(
+        clippy::significant_drop_tightening,
+        reason = "the lock is held for the whole transaction"
+    )]
+    #[inline]
+    pub fn begin_paper(
+        &self,
+        path: &Path,
+        name: &str,
+        page_count: u32,
+    ) -> Result<i64, String> {
+        let record_error =
+            |e: rusqlite::Error| format!("Failed to record paper: {e}");
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction().map_err(record_error)?;
+        let paper_id: i64 = transaction
+            .query_row(
+                UPSERT_PAPER,
+                params![path.to_string_lossy(), name, page_count, self.run_id],
+                |row| row.get(0),
+            )
+            .map_err(record_error)?;
+        transaction
+            .execute(
+                DELETE_REMOVED_EXTRACTIONS,
+                params![paper_id, self.field_names],
+            )
+            .map_err(record_error)?;
+        transaction
+            .execute(DELETE_UNUSED_FIELDS, params![self.field_names])
+            .map_err(record_error)?;
+        transaction.commit().map_err(record_error)?;
+        Ok(paper_id)
+    }
+
+    /// Inserts or replaces the stored answers for the given fields.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the answers cannot be written.
+    #[expect(
+        clippy::significant_drop_tightening,
+        reason = "the lock is held for the whole transaction"
+    )]
+    #[inline]
+    pub fn upsert_fields<'a, I>(
+        &self,
+        paper_id: i64,
+        fields: I,
+    ) -> Result<(), String>
+    where
+        I: IntoIterator<Item = (&'a String, &'a ExtractedField)>,
+    {
+        let updated_at = now();
+        let mut connection = self.connection()?;
+        let transaction = connection
+            .transaction()
+            .map_err(|e| format!("Failed to write extractions: {e}"))?;
+        for (field_name, field) in fields {
+            let value_json = field
+                .value
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()
+                .map_err(|e| e.to_string())?;
+            let warnings = serde_json::to_string(&field.warnings)
+                .map_err(|e| e.to_string())?;
+            let candidates = serde_json::to_string(&field.candidates)
+                .map_err(|e| e.to_string())?;
+            transaction
+                .execute(
+                    UPSERT_EXTRACTION,
+                    params![
+                        paper_id,
+                        field_name,
+                        field.value.as_ref().map(value_text),
+                        value_json,
+                        field.match_type,
+                        field.comment,
+                        field.page,
+                        field.bbox.map(|bbox| bbox.xmin),
+                        field.bbox.map(|bbox| bbox.ymin),
+                        field.bbox.map(|bbox| bbox.xmax),
+                        field.bbox.map(|bbox| bbox.ymax),
+                        warnings,
+                        candidates,
+                        self.run_id,
+                        updated_at
+                    ],
+                )
+                .map_err(|e| format!("Failed to write extractions: {e}"))?;
+        }
+        transaction
+            .commit()
+            .map_err(|e| format!("Failed to write extractions: {e}"))
+    }
+
+    /// Marks a paper as done.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the paper cannot be updated.
+    #[inline]
+    pub fn finish_paper(&self, paper_id: i64) -> Result<(), String> {
+        self.connection()?
+            .execute(
+                "UPDATE papers SET status = 'done' WHERE id = ?1",
+                params![paper_id],
+            )
+            .map(drop)
+            .map_err(|e| format!("Failed to record paper: {e}"))
+    }
+
+    /// Records when the run finished.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the run cannot be updated.
+    #[inline]
+    pub fn finish_run(&self) -> Result<(), String> {
+        self.connection()?
+            .execute(
+                "UPDATE runs SET finished_at = ?1 WHERE id = ?2",
+                params![now(), self.run_id],
+            )
+            .map(drop)
+            .map_err(|e| format!("Failed to record run: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::SchemaKind;
+    use crate::testing::TestDir;
+    use serde_json::Value;
+
+    fn run() -> RunInfo<'static> {
+        RunInfo {
+            model: "test-model",
+            schema_path: "schema.csv",
+            input: "papers",
+            input_mode: "pdf",
+            prompt_hash: "abc123",
+        }
+    }
+
+    fn field(value: &str, match_type: &str) -> ExtractedField {
+        ExtractedField {
+            value: Some(Value::from(value)),
+            match_type: match_type.to_owned(),
+            comment: None,
+            page: Some(2),
+            bbox: None,
+            warnings: Vec::new(),
+            candidates: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reruns_update_rows_in_place() {
+        let dir = TestDir::new("sqlite-reruns");
+        let path = dir.join("results.db");
+        let graft_type = SchemaField {
+            description: "Graft material".to_owned(),
+            ..SchemaField::new("graft_type", SchemaKind::Text)
+        };
+        let dropped = SchemaField::new("mean_age", SchemaKind::Number);
+
+        for (fields, value, match_type) in [
+            (vec![graft_type.clone(), dropped], "xenograft", "inferred"),
+            (vec![graft_type], "autograft", "found"),
+        ] {
+            let sink = SqliteSink::open(&path, &fields, &run()).unwrap();
+            let paper_id = sink
+                .begin_paper(Path::new("papers/a.pdf"), "a.pdf", 9)
+                .unwrap();
+            let extracted = field(value, match_type);
+            let answers = fields
+                .iter()
+                .map(|schema_field| (&schema_field.field_name, &extracted));
+            sink.upsert_fields(paper_id, answers).unwrap();
+            sink.finish_paper(paper_id).unwrap();
+            sink.finish_run().unwrap();
+        }
+
+        let connection = Connection::open(&path).unwrap();
+        let rows: (i64, String, String) = connection
+            .query_row(
+                "SELECT COUNT(*), MAX(value), MAX(match_type) FROM extractions",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        let counts: (i64, i64) = connection
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM runs), (SELECT COUNT(*) FROM fields)",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+
+        assert_eq!(rows, (1, "autograft".to_owned(), "found".to_owned()));
+        assert_eq!(counts, (2, 1));
+    }
+}
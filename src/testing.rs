@@ -0,0 +1,31 @@
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+/// A fresh directory for one test, unique to the process so parallel runs
+/// do not share files, and removed when dropped so a failing test leaves
+/// nothing behind.
+pub struct TestDir(PathBuf);
+
+impl TestDir {
+    pub fn new(test: &str) -> Self {
+        let path = env::temp_dir()
+            .join(format!("paper-extract-{}-{test}", process::id()));
+        drop(fs::remove_dir_all(&path));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        drop(fs::remove_dir_all(&self.0));
+    }
+}
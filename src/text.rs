@@ -0,0 +1,601 @@
+#![expect(
+    clippy::float_arithmetic,
+    reason = "text layout is computed in PDF user-space coordinates"
+)]
+
+use crate::extraction::BoundingBox;
+use crate::pdf::{PageSize, page_size};
+use lopdf::content::Content;
+use lopdf::{Dictionary, Document, Encoding, Object};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+/// Glyph width, as a fraction of the font size, assumed when a font does not
+/// list the width of a glyph.
+const CHAR_WIDTH: f64 = 0.5;
+
+/// Glyph widths in font dictionaries are given in thousandths of an em.
+const GLYPH_UNITS: f64 = 1000.0;
+
+/// Kerning adjustments in a `TJ` array larger than this (in thousandths of
+/// an em) are treated as word spaces.
+const SPACE_ADJUSTMENT: f64 = 200.0;
+
+/// Upper bound on padding inserted between runs on the same line, so that a
+/// two-column page does not turn into a line of blanks.
+const MAX_PADDING: usize = 8;
+
+/// Gaps wider than this many characters are padded to keep columns apart.
+const TAB_WIDTH: f64 = 3.0;
+
+const HALF: f64 = 0.5;
+
+const QUARTER: f64 = 0.25;
+
+type Matrix = [f64; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// A piece of text drawn by one text-showing operator, in PDF points with
+/// the origin at the top-left of the page.
+#[derive(Debug, Clone)]
+pub struct TextRun {
+    pub text: String,
+    pub xmin: f64,
+    pub ymin: f64,
+    pub xmax: f64,
+    pub ymax: f64,
+}
+
+impl TextRun {
+    fn height(&self) -> f64 {
+        self.ymax - self.ymin
+    }
+
+    const fn center(&self) -> f64 {
+        f64::midpoint(self.ymin, self.ymax)
+    }
+}
+
+/// Per-glyph advance widths of a font, in ems.
+struct FontMetrics {
+    two_byte: bool,
+    widths: BTreeMap<i64, f64>,
+    default_width: f64,
+}
+
+impl FontMetrics {
+    /// Reads `/Widths` from a simple font, or `/W` and `/DW` from the
+    /// descendant of a composite (`Type0`) font.
+    fn read(document: &Document, font: &Dictionary) -> Self {
+        let subtype = font.get(b"Subtype").and_then(Object::as_name);
+        if subtype.is_ok_and(|name| name == b"Type0") {
+            let descendant = font
+                .get(b"DescendantFonts")
+                .ok()
+                .and_then(|object| resolve(document, object))
+                .and_then(|object| object.as_array().ok())
+                .and_then(|fonts| fonts.first())
+                .and_then(|object| resolve(document, object))
+                .and_then(|object| object.as_dict().ok());
+            return descendant.map_or_else(
+                || Self {
+                    two_byte: true,
+                    widths: BTreeMap::new(),
+                    default_width: CHAR_WIDTH,
+                },
+                |cid_font| Self::read_cid(document, cid_font),
+            );
+        }
+
+        let first_char =
+            font.get(b"FirstChar").and_then(Object::as_i64).unwrap_or(0);
+        let widths = font
+            .get(b"Widths")
+            .ok()
+            .and_then(|object| resolve(document, object))
+            .and_then(|object| object.as_array().ok())
+            .map(|widths| {
+                (first_char..)
+                    .zip(widths)
+                    .filter_map(|(code, width)| {
+                        number(document, width).map(|w| (code, w / GLYPH_UNITS))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            two_byte: false,
+            widths,
+            default_width: CHAR_WIDTH,
+        }
+    }
+
+    /// Parses the `/W` array of a CID font, which mixes `c [w1 w2 ...]` and
+    /// `c_first c_last w` entries.
+    fn read_cid(document: &Document, cid_font: &Dictionary) -> Self {
+        let default_width = cid_font
+            .get(b"DW")
+            .ok()
+            .and_then(|object| number(document, object))
+            .unwrap_or(GLYPH_UNITS)
+            / GLYPH_UNITS;
+        let entries = cid_font
+            .get(b"W")
+            .ok()
+            .and_then(|object| resolve(document, object))
+            .and_then(|object| object.as_array().ok())
+            .cloned()
+            .unwrap_or_default();
+
+        let mut widths = BTreeMap::new();
+        let mut rest = entries.iter();
+        while let Some(first) =
+            rest.next().and_then(|object| object.as_i64().ok())
+        {
+            let Some(next) = rest.next() else {
+                break;
+            };
+            if let Some(list) = resolve(document, next)
+                .and_then(|object| object.as_array().ok())
+            {
+                for (code, width) in (first..).zip(list) {
+                    if let Some(w) = number(document, width) {
+                        widths.insert(code, w / GLYPH_UNITS);
+                    }
+                }
+            } else if let (Ok(last), Some(width)) = (
+                next.as_i64(),
+                rest.next().and_then(|object| number(document, object)),
+            ) {
+                for code in first..=last {
+                    widths.insert(code, width / GLYPH_UNITS);
+                }
+            } else {
+                break;
+            }
+        }
+
+        Self {
+            two_byte: true,
+            widths,
+            default_width,
+        }
+    }
+
+    /// Character codes in a shown string, one or two bytes each.
+    fn codes(&self, bytes: &[u8]) -> Vec<i64> {
+        if self.two_byte {
+            bytes
+                .chunks(2)
+                .map(|pair| match *pair {
+                    [high, low] => i64::from(high)
+                        .saturating_mul(256)
+                        .saturating_add(i64::from(low)),
+                    _ => pair.first().map_or(0, |&byte| i64::from(byte)),
+                })
+                .collect()
+        } else {
+            bytes.iter().map(|&byte| i64::from(byte)).collect()
+        }
+    }
+
+    fn width(&self, code: i64) -> f64 {
+        self.widths
+            .get(&code)
+            .copied()
+            .unwrap_or(self.default_width)
+    }
+}
+
+fn resolve<'a>(
+    document: &'a Document,
+    object: &'a Object,
+) -> Option<&'a Object> {
+    document
+        .dereference(object)
+        .ok()
+        .map(|(_, resolved)| resolved)
+}
+
+fn number(document: &Document, object: &Object) -> Option<f64> {
+    resolve(document, object)?.as_float().ok().map(f64::from)
+}
+
+struct PageFont<'a> {
+    encoding: Encoding<'a>,
+    metrics: FontMetrics,
+}
+
+struct TextState<'a> {
+    ctm: Matrix,
+    stack: Vec<Matrix>,
+    text_matrix: Matrix,
+    line_matrix: Matrix,
+    leading: f64,
+    font_size: f64,
+    char_spacing: f64,
+    word_spacing: f64,
+    font: Option<&'a PageFont<'a>>,
+}
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    [
+        a[0].mul_add(b[0], a[1] * b[2]),
+        a[0].mul_add(b[1], a[1] * b[3]),
+        a[2].mul_add(b[0], a[3] * b[2]),
+        a[2].mul_add(b[1], a[3] * b[3]),
+        a[4].mul_add(b[0], a[5].mul_add(b[2], b[4])),
+        a[4].mul_add(b[1], a[5].mul_add(b[3], b[5])),
+    ]
+}
+
+const fn translation(tx: f64, ty: f64) -> Matrix {
+    [1.0, 0.0, 0.0, 1.0, tx, ty]
+}
+
+fn operand(operands: &[Object], index: usize) -> f64 {
+    operands
+        .get(index)
+        .and_then(|value| value.as_float().ok())
+        .map_or(0.0, f64::from)
+}
+
+fn matrix_operands(operands: &[Object]) -> Matrix {
+    [
+        operand(operands, 0),
+        operand(operands, 1),
+        operand(operands, 2),
+        operand(operands, 3),
+        operand(operands, 4),
+        operand(operands, 5),
+    ]
+}
+
+impl TextState<'_> {
+    fn move_line(&mut self, tx: f64, ty: f64) {
+        self.line_matrix = multiply(&translation(tx, ty), &self.line_matrix);
+        self.text_matrix = self.line_matrix;
+    }
+
+    /// Decodes the strings of a `Tj`/`TJ` operand list, advancing the text
+    /// matrix as glyphs are shown.
+    fn show(
+        &mut self,
+        operands: &[Object],
+        page: &PageSize,
+    ) -> Option<TextRun> {
+        let font = self.font?;
+        let start = multiply(&self.text_matrix, &self.ctm);
+        let mut text = String::new();
+        let mut advance: f64 = 0.0;
+
+        for object in operands {
+            if let Ok(items) = object.as_array() {
+                for item in items {
+                    advance += self.show_item(font, item, &mut text);
+                }
+            } else {
+                advance += self.show_item(font, object, &mut text);
+            }
+        }
+
+        self.text_matrix =
+            multiply(&translation(advance, 0.0), &self.text_matrix);
+        if text.trim().is_empty() {
+            return None;
+        }
+
+        let scale = start[2].hypot(start[3]);
+        let size = self.font_size * scale;
+        let width = advance * start[0].hypot(start[1]);
+        let x = start[4] - page.left;
+        let baseline = page.top() - start[5];
+
+        Some(TextRun {
+            text,
+            xmin: x,
+            ymin: size.mul_add(-0.8, baseline),
+            xmax: x + width,
+            ymax: size.mul_add(0.2, baseline),
+        })
+    }
+
+    /// Appends one string or kerning adjustment and returns the horizontal
+    /// advance in text space.
+    fn show_item(
+        &self,
+        font: &PageFont<'_>,
+        item: &Object,
+        text: &mut String,
+    ) -> f64 {
+        if let Ok(bytes) = item.as_str() {
+            let decoded =
+                font.encoding.bytes_to_string(bytes).unwrap_or_default();
+            text.push_str(&decoded);
+            font.metrics
+                .codes(bytes)
+                .into_iter()
+                .map(|code| {
+                    let spacing = if code == 32 && !font.metrics.two_byte {
+                        self.char_spacing + self.word_spacing
+                    } else {
+                        self.char_spacing
+                    };
+                    font.metrics.width(code).mul_add(self.font_size, spacing)
+                })
+                .sum()
+        } else if let Ok(adjustment) = item.as_float().map(f64::from) {
+            if adjustment < -SPACE_ADJUSTMENT && !text.ends_with(' ') {
+                text.push(' ');
+            }
+            -adjustment / GLYPH_UNITS * self.font_size
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Extracts positioned text runs from a page by walking its content stream.
+pub fn page_runs(
+    document: &Document,
+    page: u32,
+) -> Result<Vec<TextRun>, String> {
+    let size = page_size(document, page)
+        .ok_or_else(|| format!("Page {page} has no MediaBox"))?;
+    let page_id = *document
+        .get_pages()
+        .get(&page)
+        .ok_or_else(|| format!("Page {page} not found"))?;
+    let fonts = document
+        .get_page_fonts(page_id)
+        .map_err(|e| format!("Failed to read fonts on page {page}: {e}"))?;
+    let page_fonts: BTreeMap<Vec<u8>, PageFont<'_>> = fonts
+        .into_iter()
+        .filter_map(|(name, font)| {
+            font.get_font_encoding(document).ok().map(|encoding| {
+                let metrics = FontMetrics::read(document, font);
+                (name, PageFont { encoding, metrics })
+            })
+        })
+        .collect();
+    let content = Content::decode(&document.get_page_content(page_id))
+        .map_err(|e| format!("Failed to decode content of page {page}: {e}"))?;
+
+    let mut state = TextState {
+        ctm: IDENTITY,
+        stack: Vec::new(),
+        text_matrix: IDENTITY,
+        line_matrix: IDENTITY,
+        leading: 0.0,
+        font_size: 0.0,
+        char_spacing: 0.0,
+        word_spacing: 0.0,
+        font: None,
+    };
+    let mut runs = Vec::new();
+
+    for operation in &content.operations {
+        let operands = &operation.operands;
+        match operation.operator.as_str() {
+            "q" => state.stack.push(state.ctm),
+            "Q" => state.ctm = state.stack.pop().unwrap_or(IDENTITY),
+            "cm" => {
+                state.ctm = multiply(&matrix_operands(operands), &state.ctm);
+            }
+            "BT" => {
+                state.text_matrix = IDENTITY;
+                state.line_matrix = IDENTITY;
+            }
+            "Tf" => {
+                state.font = operands
+                    .first()
+                    .and_then(|name| name.as_name().ok())
+                    .and_then(|name| page_fonts.get(name));
+                state.font_size = operand(operands, 1);
+            }
+            "Tc" => state.char_spacing = operand(operands, 0),
+            "Tw" => state.word_spacing = operand(operands, 0),
+            "TL" => state.leading = operand(operands, 0),
+            "Td" => state.move_line(operand(operands, 0), operand(operands, 1)),
+            "TD" => {
+                state.leading = -operand(operands, 1);
+                state.move_line(operand(operands, 0), operand(operands, 1));
+            }
+            "Tm" => {
+                state.line_matrix = matrix_operands(operands);
+                state.text_matrix = state.line_matrix;
+            }
+            "T*" => state.move_line(0.0, -state.leading),
+            "Tj" | "TJ" => runs.extend(state.show(operands, &size)),
+            "'" => {
+                state.move_line(0.0, -state.leading);
+                runs.extend(state.show(operands, &size));
+            }
+            "\"" => {
+                state.move_line(0.0, -state.leading);
+                runs.extend(
+                    state.show(operands.get(2..).unwrap_or(&[]), &size),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    Ok(runs)
+}
+
+/// Groups runs into lines by vertical position and lays each line out left
+/// to right, padding wide gaps with spaces so table columns stay apart.
+pub fn layout_runs(runs: &[TextRun]) -> String {
+    let mut sorted: Vec<&TextRun> = runs.iter().collect();
+    sorted.sort_by(|a, b| a.center().total_cmp(&b.center()));
+
+    let mut lines: Vec<Vec<&TextRun>> = Vec::new();
+    for run in sorted {
+        match lines.last_mut() {
+            Some(line)
+                if line.first().is_some_and(|first| {
+                    (first.center() - run.center()).abs()
+                        < first.height().min(run.height()) * HALF
+                }) =>
+            {
+                line.push(run);
+            }
+            _ => lines.push(vec![run]),
+        }
+    }
+
+    let mut output = String::new();
+    for mut line in lines {
+        line.sort_by(|a, b| a.xmin.total_cmp(&b.xmin));
+        let mut previous: Option<&TextRun> = None;
+        for run in line {
+            if let Some(before) = previous {
+                output.push_str(&gap_padding(before, run));
+            }
+            output.push_str(&run.text);
+            previous = Some(run);
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+fn gap_padding(before: &TextRun, after: &TextRun) -> String {
+    let char_width = before.height() * CHAR_WIDTH;
+    let gap = after.xmin - before.xmax;
+    if char_width <= f64::EPSILON || gap < char_width * QUARTER {
+        return String::new();
+    }
+
+    let mut padding = String::from(" ");
+    let mut covered = char_width * TAB_WIDTH;
+    while covered < gap && padding.len() < MAX_PADDING {
+        padding.push(' ');
+        covered += char_width;
+    }
+    padding
+}
+
+/// Renders the given pages as plain text with `--- Page N ---` markers,
+/// numbering pages from 1 in the order given.
+pub fn document_text(
+    document: &Document,
+    pages: &[u32],
+) -> Result<String, String> {
+    let mut output = String::new();
+
+    for (index, &page) in pages.iter().enumerate() {
+        let runs = page_runs(document, page)?;
+        writeln!(&mut output, "--- Page {} ---", index.saturating_add(1))
+            .map_err(|e| e.to_string())?;
+        output.push_str(&layout_runs(&runs));
+        output.push('\n');
+    }
+
+    Ok(output)
+}
+
+/// Finds the first run on a page whose text contains `needle`, ignoring
+/// case and whitespace differences.
+pub fn locate_text(runs: &[TextRun], needle: &str) -> Option<TextRun> {
+    let wanted = normalize(needle);
+    if wanted.is_empty() {
+        return None;
+    }
+    runs.iter()
+        .find(|run| normalize(&run.text).contains(&wanted))
+        .cloned()
+}
+
+/// Returns the text of the runs whose centre lies inside `bbox`, laid out
+/// as lines, so a reviewer can see what the model pointed at.
+pub fn evidence_text(runs: &[TextRun], bbox: &BoundingBox) -> String {
+    let inside: Vec<TextRun> = runs
+        .iter()
+        .filter(|run| {
+            let x = (run.xmin + run.xmax) * HALF;
+            let y = (run.ymin + run.ymax) * HALF;
+            (bbox.xmin..=bbox.xmax).contains(&x)
+                && (bbox.ymin..=bbox.ymax).contains(&y)
+        })
+        .cloned()
+        .collect();
+    layout_runs(&inside).trim().to_owned()
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str, xmin: f64, ymin: f64) -> TextRun {
+        TextRun {
+            text: text.to_owned(),
+            xmin,
+            ymin,
+            xmax: f64::from(u32::try_from(text.len()).unwrap())
+                .mul_add(5.0, xmin),
+            ymax: ymin + 10.0,
+        }
+    }
+
+    #[test]
+    fn runs_on_the_same_baseline_share_a_line() {
+        let runs = vec![
+            run("Mean", 10.0, 100.0),
+            run("second", 10.0, 120.0),
+            run("12.1", 200.0, 101.0),
+        ];
+        let text = layout_runs(&runs);
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("Mean "));
+        assert!(lines[0].ends_with("12.1"));
+        assert_eq!(lines[1], "second");
+    }
+
+    #[test]
+    fn adjacent_runs_are_not_padded() {
+        let runs = vec![run("ab", 10.0, 0.0), run("cd", 20.0, 0.0)];
+        assert_eq!(layout_runs(&runs), "abcd\n");
+    }
+
+    #[test]
+    fn locate_ignores_case_and_spacing() {
+        let runs =
+            vec![run("Age 45 years", 10.0, 0.0), run("n = 17", 10.0, 20.0)];
+        let found = locate_text(&runs, "N=17").unwrap();
+        assert!((found.ymin - 20.0).abs() < f64::EPSILON);
+        assert!(locate_text(&runs, "").is_none());
+        assert!(locate_text(&runs, "missing").is_none());
+    }
+
+    #[test]
+    fn evidence_keeps_runs_inside_the_box() {
+        let runs = vec![
+            run("Mean age", 10.0, 100.0),
+            run("45.2", 80.0, 100.0),
+            run("Footer", 10.0, 700.0),
+        ];
+        let bbox = BoundingBox {
+            xmin: 0.0,
+            ymin: 95.0,
+            xmax: 200.0,
+            ymax: 115.0,
+        };
+        let evidence = evidence_text(&runs, &bbox);
+        assert!(evidence.contains("Mean age"));
+        assert!(evidence.contains("45.2"));
+        assert!(!evidence.contains("Footer"));
+    }
+}
@@ -0,0 +1,76 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Tokens reported by the provider for one or more requests.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    /// Prompt tokens served from the provider's prompt cache.
+    pub cached_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl Usage {
+    /// Reads the `usage` object of a chat completions response. Providers
+    /// that report nothing count as zero.
+    #[must_use]
+    #[inline]
+    pub fn from_response(response: &Value) -> Self {
+        let count = |pointer: &str| {
+            response
+                .pointer(pointer)
+                .and_then(Value::as_u64)
+                .unwrap_or_default()
+        };
+        Self {
+            prompt_tokens: count("/usage/prompt_tokens"),
+            cached_tokens: count("/usage/prompt_tokens_details/cached_tokens"),
+            completion_tokens: count("/usage/completion_tokens"),
+        }
+    }
+
+    /// Adds the tokens of another request or paper to this total.
+    #[inline]
+    pub const fn include(&mut self, other: Self) {
+        self.prompt_tokens =
+            self.prompt_tokens.saturating_add(other.prompt_tokens);
+        self.cached_tokens =
+            self.cached_tokens.saturating_add(other.cached_tokens);
+        self.completion_tokens = self
+            .completion_tokens
+            .saturating_add(other.completion_tokens);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_cached_prompt_tokens() {
+        let prompt: u64 = 12_000;
+        let cached: u64 = 9_500;
+        let completion: u64 = 800;
+        let response = json!({
+            "usage": {
+                "prompt_tokens": prompt,
+                "completion_tokens": completion,
+                "prompt_tokens_details": {"cached_tokens": cached}
+            }
+        });
+
+        let mut total = Usage::from_response(&response);
+        total.include(Usage::from_response(&json!({})));
+        total.include(Usage::from_response(&response));
+        assert_eq!(
+            total,
+            Usage {
+                prompt_tokens: prompt * 2,
+                cached_tokens: cached * 2,
+                completion_tokens: completion * 2,
+            }
+        );
+    }
+}
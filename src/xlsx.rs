@@ -0,0 +1,271 @@
+use crate::extraction::{ExtractedField, ExtractionResult, value_text};
+use crate::output::{CSV_HEADERS, candidates_text};
+use crate::schema::SchemaField;
+use rust_xlsxwriter::{
+    ColNum, Color, Format, Note, RowNum, Workbook, Worksheet, XlsxError,
+};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Name of the sheet with one row per paper.
+const SUMMARY_SHEET: &str = "Summary";
+
+/// Longest sheet name Excel accepts.
+const MAX_SHEET_NAME: usize = 31;
+
+/// Characters Excel does not allow in sheet names.
+const INVALID_SHEET_CHARS: [char; 7] = ['[', ']', ':', '*', '?', '/', '\\'];
+
+/// Author shown on cell notes.
+const NOTE_AUTHOR: &str = "paper-extract";
+
+/// Background colour for each match type, using Excel's good, neutral and
+/// bad cell styles, with lavender for conflicting values and grey for
+/// fields that do not apply.
+fn match_format(match_type: &str) -> Format {
+    let colour = match match_type {
+        "found" => 0x00C6_EFCE,
+        "inferred" => 0x00FF_EB9C,
+        "ambiguous" => 0x00E4_DFEC,
+        "not_applicable" => 0x00E7_E6E6,
+        _ => 0x00FF_C7CE,
+    };
+    Format::new().set_background_color(Color::RGB(colour))
+}
+
+/// The results of one paper, named after its file.
+#[non_exhaustive]
+pub struct PaperResults {
+    pub name: String,
+    pub results: ExtractionResult,
+}
+
+/// Writes a workbook with a summary sheet and one sheet per paper.
+///
+/// The summary is wide, one row per paper with its name, the run's prompt
+/// hash and one column per field; paper sheets use the CSV layout. Cells are
+/// coloured by match type and comments are attached as notes.
+///
+/// # Errors
+///
+/// Fails when the workbook cannot be built or saved.
+#[expect(
+    clippy::module_name_repetitions,
+    reason = "pairs with `write_csv` and `write_json`"
+)]
+#[inline]
+pub fn write_xlsx(
+    path: &str,
+    prompt_hash: &str,
+    fields: &[SchemaField],
+    papers: &[PaperResults],
+) -> Result<(), String> {
+    let mut workbook = Workbook::new();
+    build_workbook(&mut workbook, prompt_hash, fields, papers)
+        .and_then(|()| workbook.save(path))
+        .map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+fn build_workbook(
+    workbook: &mut Workbook,
+    prompt_hash: &str,
+    fields: &[SchemaField],
+    papers: &[PaperResults],
+) -> Result<(), XlsxError> {
+    let header = Format::new().set_bold();
+
+    let summary = workbook.add_worksheet();
+    summary.set_name(SUMMARY_SHEET)?;
+    summary.write_string_with_format(0, 0, "paper", &header)?;
+    summary.write_string_with_format(0, 1, "prompt_hash", &header)?;
+    for (col, field) in (2..).zip(fields) {
+        summary.write_string_with_format(0, col, &field.field_name, &header)?;
+    }
+    for (row, paper) in (1..).zip(papers) {
+        summary.write_string(row, 0, &paper.name)?;
+        summary.write_string(row, 1, prompt_hash)?;
+        for (col, field) in (2..).zip(fields) {
+            if let Some(extracted) = paper.results.get(&field.field_name) {
+                write_value(summary, row, col, extracted)?;
+            }
+        }
+    }
+    summary.set_freeze_panes(1, 1)?;
+    summary.autofit();
+
+    let mut used_names = HashSet::from([SUMMARY_SHEET.to_lowercase()]);
+    for paper in papers {
+        let sheet = workbook.add_worksheet();
+        sheet.set_name(sheet_name(&paper.name, &mut used_names))?;
+        write_paper_sheet(sheet, fields, &paper.results, &header)?;
+    }
+    Ok(())
+}
+
+fn write_paper_sheet(
+    sheet: &mut Worksheet,
+    fields: &[SchemaField],
+    results: &ExtractionResult,
+    header: &Format,
+) -> Result<(), XlsxError> {
+    for (col, title) in (0..).zip(CSV_HEADERS) {
+        sheet.write_string_with_format(0, col, title, header)?;
+    }
+
+    for (row, field) in (1..).zip(fields) {
+        sheet.write_string(row, 0, &field.field_name)?;
+        let Some(extracted) = results.get(&field.field_name) else {
+            continue;
+        };
+        write_value(sheet, row, 1, extracted)?;
+        sheet.write_string_with_format(
+            row,
+            2,
+            &extracted.match_type,
+            &match_format(&extracted.match_type),
+        )?;
+        if let Some(comment) = extracted.comment.as_deref() {
+            sheet.write_string(row, 3, comment)?;
+        }
+        if let Some(page) = extracted.page {
+            sheet.write_number(row, 4, page)?;
+        }
+        if let Some(bbox) = extracted.bbox {
+            for (col, coordinate) in
+                (5..).zip([bbox.xmin, bbox.ymin, bbox.xmax, bbox.ymax])
+            {
+                sheet.write_number(row, col, coordinate)?;
+            }
+        }
+        if !extracted.candidates.is_empty() {
+            sheet.write_string(
+                row,
+                9,
+                candidates_text(&extracted.candidates),
+            )?;
+        }
+    }
+
+    sheet.set_freeze_panes(1, 1)?;
+    sheet.autofit();
+    Ok(())
+}
+
+/// Writes a value as a typed, coloured cell, with the comment as a note.
+fn write_value(
+    sheet: &mut Worksheet,
+    row: RowNum,
+    col: ColNum,
+    field: &ExtractedField,
+) -> Result<(), XlsxError> {
+    let format = match_format(&field.match_type);
+    let value = field.value.as_ref().filter(|value| !value.is_null());
+    if let Some(number) = value.and_then(Value::as_f64) {
+        sheet.write_number_with_format(row, col, number, &format)?;
+    } else if let Some(flag) = value.and_then(Value::as_bool) {
+        sheet.write_boolean_with_format(row, col, flag, &format)?;
+    } else if let Some(text) = value.map(value_text) {
+        sheet.write_string_with_format(row, col, text, &format)?;
+    } else {
+        sheet.write_blank(row, col, &format)?;
+    }
+
+    if let Some(comment) = field
+        .comment
+        .as_deref()
+        .filter(|comment| !comment.is_empty())
+    {
+        let note = Note::new(comment).set_author(NOTE_AUTHOR);
+        sheet.insert_note(row, col, &note)?;
+    }
+    Ok(())
+}
+
+/// Turns a paper name into a valid, unique sheet name: invalid characters
+/// are replaced, the name is shortened to Excel's limit, and repeats get a
+/// numeric suffix. Excel compares sheet names case-insensitively.
+fn sheet_name(paper: &str, used: &mut HashSet<String>) -> String {
+    let cleaned: String = paper
+        .trim_end_matches(".pdf")
+        .chars()
+        .map(|c| {
+            if INVALID_SHEET_CHARS.contains(&c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let trimmed = cleaned.trim_matches('\'');
+    let base = if trimmed.is_empty() { "paper" } else { trimmed };
+
+    let mut candidate: String = base.chars().take(MAX_SHEET_NAME).collect();
+    let mut counter: u32 = 1;
+    while !used.insert(candidate.to_lowercase()) {
+        counter = counter.saturating_add(1);
+        let suffix = format!(" ({counter})");
+        let keep = MAX_SHEET_NAME.saturating_sub(suffix.chars().count());
+        candidate = base.chars().take(keep).collect();
+        candidate.push_str(&suffix);
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::SchemaKind;
+    use crate::testing::TestDir;
+    use std::fs;
+
+    #[test]
+    fn workbook_has_summary_and_paper_sheets() {
+        let fields = [SchemaField::new("sample_size", SchemaKind::Number)];
+        let sample_size: u32 = 24;
+        let extracted = ExtractedField {
+            value: Some(Value::from(sample_size)),
+            match_type: "found".to_owned(),
+            comment: Some("Two arms of twelve".to_owned()),
+            page: Some(3),
+            bbox: None,
+            warnings: Vec::new(),
+            candidates: Vec::new(),
+        };
+        let papers = ["a.pdf", "b.pdf"].map(|name| PaperResults {
+            name: name.to_owned(),
+            results: ExtractionResult::from([(
+                "sample_size".to_owned(),
+                extracted.clone(),
+            )]),
+        });
+
+        let mut workbook = Workbook::new();
+        build_workbook(&mut workbook, "abc123", &fields, &papers).unwrap();
+        let names: Vec<String> =
+            workbook.worksheets().iter().map(Worksheet::name).collect();
+        assert_eq!(names, ["Summary", "a", "b"]);
+
+        let dir = TestDir::new("xlsx-workbook");
+        let path = dir.join("results.xlsx");
+        write_xlsx(path.to_str().unwrap(), "abc123", &fields, &papers).unwrap();
+        let data = fs::read(&path).unwrap();
+        assert!(data.starts_with(b"PK"));
+    }
+
+    #[test]
+    fn sheet_names_are_valid_and_unique() {
+        let mut used = HashSet::from([SUMMARY_SHEET.to_lowercase()]);
+        assert_eq!(
+            sheet_name("smith [2019]: RCT?.pdf", &mut used),
+            "smith _2019__ RCT_"
+        );
+        assert_eq!(sheet_name("summary", &mut used), "summary (2)");
+
+        let long = "a".repeat(40);
+        let first = sheet_name(&long, &mut used);
+        let second = sheet_name(&long, &mut used);
+        assert_eq!(first.chars().count(), MAX_SHEET_NAME);
+        assert_eq!(second.chars().count(), MAX_SHEET_NAME);
+        assert!(second.ends_with(" (2)"));
+    }
+}